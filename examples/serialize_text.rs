@@ -5,13 +5,10 @@ use ndef::{Message, Payload, Record, RecordType};
 
 fn main() {
     let mut msg = Message::default();
-    let txt = "NDEF Text from Rust🦀!";
-    #[cfg(feature = "alloc")]
-    let txt = txt.to_string();
-    let rec1 = Record::new(None, Payload::RTD(RecordType::Text { enc: "en", txt }));
-    #[cfg(feature = "alloc")]
-    msg.append_record(rec1);
-    #[cfg(not(feature = "alloc"))]
+    let rec1 = Record::new(
+        None,
+        Payload::RTD(RecordType::text("en", "NDEF Text from Rust🦀!")),
+    );
     msg.append_record(rec1).unwrap();
 
     // Print message raw data
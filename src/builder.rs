@@ -0,0 +1,237 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use core::num::NonZeroUsize;
+
+use crate::{id, Error, Message, Payload, Record, RecordType, Result};
+
+/// Builds a [`Message`] one record at a time, see [`Message::builder`].
+///
+/// Each record is checked as it is added, like [`Message::validate`] would.
+/// The first problem found is kept and returned by [`MessageBuilder::build`],
+/// and the calls after it are ignored, so a message is built in a single
+/// chain whether `alloc` is enabled or not.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MessageBuilder<'a> {
+    message: Message<'a>,
+    error: Option<Error>,
+}
+
+impl<'a> Message<'a> {
+    /// Starts building a message.
+    pub fn builder() -> MessageBuilder<'a> {
+        MessageBuilder::default()
+    }
+}
+
+impl<'a> MessageBuilder<'a> {
+    /// Runs `edit` on the message unless a problem was already found.
+    fn edit(mut self, edit: impl FnOnce(&mut Message<'a>) -> Result<()>) -> Self {
+        if self.error.is_none() {
+            self.error = edit(&mut self.message).err();
+        }
+        self
+    }
+
+    /// Adds `record` at the end of the message.
+    pub fn record(self, record: Record<'a>) -> Self {
+        self.edit(|message| {
+            check(&record)?;
+            message.append_record(record)
+        })
+    }
+
+    /// Adds a Text record, see [`RecordType::text`].
+    pub fn text(self, enc: &'a str, txt: &'a str) -> Self {
+        self.record(Record::new(None, Payload::RTD(RecordType::text(enc, txt))))
+    }
+
+    /// Adds a URI record, see [`RecordType::uri`].
+    pub fn uri(self, uri: &'a str) -> Self {
+        self.record(Record::new(None, Payload::RTD(RecordType::uri(uri))))
+    }
+
+    /// Adds an external record, see [`RecordType::external`].
+    pub fn external(self, domain: &'a str, type_: &'a str, data: &'a [u8]) -> Self {
+        self.record(Record::new(
+            None,
+            Payload::RTD(RecordType::external(domain, type_, data)),
+        ))
+    }
+
+    /// Adds a media record, see [`Payload::media`].
+    pub fn media(self, type_: &'a str, data: &'a [u8]) -> Self {
+        self.record(Record::new(None, Payload::media(type_, data)))
+    }
+
+    /// Adds a CBOR record, see [`RecordType::cbor`].
+    #[cfg(feature = "cbor")]
+    pub fn cbor(self, data: &'a [u8]) -> Self {
+        self.record(Record::new(None, Payload::RTD(RecordType::cbor(data))))
+    }
+
+    /// Gives `id` to the last record added. The ID must not be carried by
+    /// another record, see [`Message::unique_id`] for one that is not.
+    pub fn id(self, id: &'a [u8]) -> Self {
+        self.edit(|message| {
+            if message.find_id(id).is_some() {
+                return Err(Error::DuplicateId);
+            }
+            let record = message.records.last_mut().ok_or(Error::EmptyMessage)?;
+            record.set_id(Some(id));
+            check(record)
+        })
+    }
+
+    /// Gives the last record added an ID no other record carries, generated by
+    /// [`Message::unique_id`].
+    pub fn unique_id(self) -> Self {
+        self.edit(|message| {
+            let id = message.unique_id();
+            let record = message.records.last_mut().ok_or(Error::EmptyMessage)?;
+            record.id = Some(id::generated(id));
+            check(record)
+        })
+    }
+
+    /// Writes the payload of the last record added in chunks of at most
+    /// `size` bytes, see [`Record::set_chunk_size`].
+    pub fn chunk_size(self, size: NonZeroUsize) -> Self {
+        self.edit(|message| {
+            let record = message.records.last_mut().ok_or(Error::EmptyMessage)?;
+            record.set_chunk_size(Some(size));
+            Ok(())
+        })
+    }
+
+    /// The message built, or the first problem found while building it. A
+    /// message without records is refused with [`Error::EmptyMessage`].
+    pub fn build(self) -> Result<Message<'a>> {
+        match self.error {
+            Some(error) => Err(error),
+            None if self.message.records.is_empty() => Err(Error::EmptyMessage),
+            None => Ok(self.message),
+        }
+    }
+}
+
+/// First problem the record has on its own, see [`Record::check`].
+fn check(record: &Record<'_>) -> Result<()> {
+    let mut checked = Ok(());
+    record.check(&mut |error| {
+        if checked.is_ok() {
+            checked = Err(error);
+        }
+    });
+    checked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let msg = Message::builder()
+            .text("en", "hi")
+            .id(b"t")
+            .uri("https://www.example.com")
+            .external("ex.com", "t", b"x")
+            .build()
+            .unwrap();
+        assert_eq!(msg.records().len(), 3);
+        assert_eq!(msg.records()[0].id(), Some(b"t".as_slice()));
+        assert_eq!(
+            msg.records()[1].payload,
+            Payload::RTD(RecordType::Uri {
+                prefix: 2,
+                uri: crate::borrowed("example.com"),
+            })
+        );
+        assert_eq!(Message::try_from(msg.to_vec().unwrap().as_slice()), Ok(msg));
+
+        // The first problem is kept, whatever follows it.
+        let built = Message::builder()
+            .text("en", "hi")
+            .external("ex.com", "", b"x")
+            .text("", "hi")
+            .build();
+        assert_eq!(built, Err(Error::InvalidExternalType("".into())));
+        let built = Message::builder()
+            .text("en", "a")
+            .id(b"a")
+            .text("en", "b")
+            .id(b"a")
+            .build();
+        assert_eq!(built, Err(Error::DuplicateId));
+        assert_eq!(
+            Message::builder().id(b"a").build(),
+            Err(Error::EmptyMessage)
+        );
+        // an ID is checked against the record it is given to
+        assert_eq!(
+            Message::builder()
+                .record(Record::new(None, Payload::Empty))
+                .id(b"x")
+                .build(),
+            Err(Error::NonEmptyField(crate::Field::Id))
+        );
+        assert_eq!(
+            Message::builder()
+                .record(Record::new(None, Payload::Empty))
+                .unique_id()
+                .build(),
+            Err(Error::NonEmptyField(crate::Field::Id))
+        );
+        assert_eq!(
+            Message::builder().text("en", "a").id(&[0; 256]).build(),
+            Err(Error::FieldTooLong)
+        );
+        assert_eq!(Message::builder().build(), Err(Error::EmptyMessage));
+    }
+
+    /// Generated IDs skip the ones given by hand.
+    #[test]
+    fn test_builder_unique_id() {
+        let msg = Message::builder()
+            .text("en", "a")
+            .unique_id()
+            .text("en", "b")
+            .id(b"2")
+            .text("en", "c")
+            .unique_id()
+            .build()
+            .unwrap();
+        assert!(msg.records().iter().map(Record::id).eq([
+            Some(b"1".as_slice()),
+            Some(b"2"),
+            Some(b"3")
+        ]));
+        assert_eq!(
+            Message::builder().unique_id().build(),
+            Err(Error::EmptyMessage)
+        );
+    }
+
+    /// A chunked payload is split on the wire and only its initial chunk
+    /// carries the type and ID.
+    #[test]
+    fn test_builder_chunks() {
+        let msg = Message::builder()
+            .external("ex.com", "t", b"abcde")
+            .id(b"i")
+            .chunk_size(NonZeroUsize::new(2).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(
+            msg.to_vec().unwrap().as_slice(),
+            [
+                0xBC, 0x08, 0x02, 0x01, b'e', b'x', b'.', b'c', b'o', b'm', b':', b't', b'i', b'a',
+                b'b', 0x36, 0x00, 0x02, b'c', b'd', 0x56, 0x00, 0x01, b'e',
+            ]
+        );
+        #[cfg(feature = "alloc")]
+        assert_eq!(Message::try_from(msg.to_vec().unwrap().as_slice()), Ok(msg));
+    }
+}
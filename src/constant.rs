@@ -0,0 +1,354 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(feature = "cbor")]
+use crate::CBOR_TYPE;
+use crate::{
+    is_external_domain, is_external_type, is_language, is_media_type, uri_prefix_code, Header,
+    TypeNameFormat, MAX_FIELD_LEN, MAX_SHORT_PAYLOAD_LEN, URI_PREFIXES,
+};
+
+/// Serializes a message at compile time. It takes the records of the message
+/// as [`ConstRecord`] constants and expands to a `[u8; N]` array of exactly the
+/// message length, ready to be embedded in a `static`:
+///
+/// ```
+/// static MESSAGE: &[u8] = &ndef::message![
+///     ndef::ConstRecord::uri("https://www.example.com"),
+///     ndef::ConstRecord::text("en", "hi").id(b"t"),
+/// ];
+/// ```
+///
+/// A record the serializer would refuse fails the build instead, so the bytes
+/// are those [`crate::Message::to_vec`] writes for the same records:
+///
+/// ```compile_fail
+/// static MESSAGE: &[u8] = &ndef::message![ndef::ConstRecord::external("ex.com", "", b"")];
+/// ```
+#[macro_export]
+macro_rules! message {
+    ($($record:expr),+ $(,)?) => {{
+        const RECORDS: &[$crate::ConstRecord<'static>] = &[$($record),+];
+        const MESSAGE: [u8; $crate::ConstRecord::message_len(RECORDS)] =
+            $crate::ConstRecord::message(RECORDS);
+        MESSAGE
+    }};
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Kind<'a> {
+    Text {
+        enc: &'a str,
+        txt: &'a str,
+    },
+    Uri {
+        prefix: u8,
+        uri: &'a [u8],
+    },
+    External {
+        domain: &'a str,
+        type_: &'a str,
+        data: &'a [u8],
+    },
+    Media {
+        type_: &'a str,
+        data: &'a [u8],
+    },
+    #[cfg(feature = "cbor")]
+    Cbor(&'a [u8]),
+}
+
+/// Record of a message serialized at compile time by [`message!`]. Its
+/// constructors check the record like the serializer does, and panic when it
+/// would refuse it, which is a build error in a constant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConstRecord<'a> {
+    id: Option<&'a [u8]>,
+    kind: Kind<'a>,
+}
+
+/// Serialized bytes of a message being written in a constant, where a `&mut`
+/// is not available on the minimum supported Rust version.
+struct Writer<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Writer<N> {
+    const fn push(mut self, byte: u8) -> Self {
+        if self.len == N {
+            panic!("the message is longer than its array");
+        }
+        self.buf[self.len] = byte;
+        self.len += 1;
+        self
+    }
+
+    const fn extend(mut self, bytes: &[u8]) -> Self {
+        let mut i = 0;
+        while i < bytes.len() {
+            self = self.push(bytes[i]);
+            i += 1;
+        }
+        self
+    }
+}
+
+impl<'a> ConstRecord<'a> {
+    const fn new(kind: Kind<'a>) -> Self {
+        Self { id: None, kind }
+    }
+
+    /// Text record, encoded in UTF-8.
+    pub const fn text(enc: &'a str, txt: &'a str) -> Self {
+        if !is_language(enc.as_bytes()) {
+            panic!("invalid language code");
+        }
+        Self::new(Kind::Text { enc, txt })
+    }
+
+    /// URI record, abbreviated like [`crate::RecordType::uri`].
+    pub const fn uri(uri: &'a str) -> Self {
+        let prefix = uri_prefix_code(uri.as_bytes());
+        let (_, uri) = uri.as_bytes().split_at(URI_PREFIXES[prefix].len());
+        Self::new(Kind::Uri {
+            prefix: prefix as u8,
+            uri,
+        })
+    }
+
+    /// External record of type `domain:type_`.
+    pub const fn external(domain: &'a str, type_: &'a str, data: &'a [u8]) -> Self {
+        if !is_external_domain(domain.as_bytes()) || !is_external_type(type_.as_bytes()) {
+            panic!("invalid external type name");
+        }
+        Self::new(Kind::External {
+            domain,
+            type_,
+            data,
+        })
+    }
+
+    /// Media record of type `type_`.
+    pub const fn media(type_: &'a str, data: &'a [u8]) -> Self {
+        if !is_media_type(type_.as_bytes()) {
+            panic!("invalid media type");
+        }
+        Self::new(Kind::Media { type_, data })
+    }
+
+    /// CBOR record.
+    #[cfg(feature = "cbor")]
+    pub const fn cbor(data: &'a [u8]) -> Self {
+        Self::new(Kind::Cbor(data))
+    }
+
+    /// The record, carrying the ID `id`.
+    pub const fn id(mut self, id: &'a [u8]) -> Self {
+        if id.len() > MAX_FIELD_LEN {
+            panic!("ID is longer than its length can announce");
+        }
+        self.id = Some(id);
+        self
+    }
+
+    const fn type_name_format(&self) -> TypeNameFormat {
+        match self.kind {
+            Kind::Text { .. } | Kind::Uri { .. } => TypeNameFormat::NfcWellKnown,
+            Kind::External { .. } => TypeNameFormat::NfcExternal,
+            #[cfg(feature = "cbor")]
+            Kind::Cbor(_) => TypeNameFormat::NfcExternal,
+            Kind::Media { .. } => TypeNameFormat::Media,
+        }
+    }
+
+    const fn type_len(&self) -> usize {
+        let len = match self.kind {
+            Kind::Text { .. } | Kind::Uri { .. } => 1,
+            Kind::External { domain, type_, .. } => domain.len() + 1 + type_.len(),
+            Kind::Media { type_, .. } => type_.len(),
+            #[cfg(feature = "cbor")]
+            Kind::Cbor(_) => CBOR_TYPE.len(),
+        };
+        if len > MAX_FIELD_LEN {
+            panic!("type name is longer than its length can announce");
+        }
+        len
+    }
+
+    const fn payload_len(&self) -> usize {
+        let len = match self.kind {
+            Kind::Text { enc, txt } => 1 + enc.len() + txt.len(),
+            Kind::Uri { uri, .. } => 1 + uri.len(),
+            Kind::External { data, .. } | Kind::Media { data, .. } => data.len(),
+            #[cfg(feature = "cbor")]
+            Kind::Cbor(data) => data.len(),
+        };
+        if len > u32::MAX as usize {
+            panic!("payload is longer than its length can announce");
+        }
+        len
+    }
+
+    const fn len(&self) -> usize {
+        let payload_len = self.payload_len();
+        let id_len = match self.id {
+            Some(id) => 1 + id.len(),
+            None => 0,
+        };
+        let payload_length_len = if payload_len <= MAX_SHORT_PAYLOAD_LEN {
+            1
+        } else {
+            4
+        };
+        2 + payload_length_len + id_len + self.type_len() + payload_len
+    }
+
+    const fn write<const N: usize>(&self, mut buf: Writer<N>, begin: bool, end: bool) -> Writer<N> {
+        let payload_len = self.payload_len();
+        // Header
+        let mut header = self.type_name_format().code();
+        if begin {
+            header |= Header::MESSAGE_BEGIN;
+        }
+        if end {
+            header |= Header::MESSAGE_END;
+        }
+        if payload_len <= MAX_SHORT_PAYLOAD_LEN {
+            header |= Header::SHORT_RECORD;
+        }
+        if self.id.is_some() {
+            header |= Header::ID_LENGTH;
+        }
+        buf = buf.push(header);
+        // Type Length
+        buf = buf.push(self.type_len() as u8);
+        // Payload Length
+        buf = if payload_len <= MAX_SHORT_PAYLOAD_LEN {
+            buf.push(payload_len as u8)
+        } else {
+            buf.extend(&(payload_len as u32).to_be_bytes())
+        };
+        // ID Length
+        if let Some(id) = self.id {
+            buf = buf.push(id.len() as u8);
+        }
+        // Type
+        buf = match self.kind {
+            Kind::Text { .. } => buf.push(b'T'),
+            Kind::Uri { .. } => buf.push(b'U'),
+            Kind::External { domain, type_, .. } => buf
+                .extend(domain.as_bytes())
+                .push(b':')
+                .extend(type_.as_bytes()),
+            Kind::Media { type_, .. } => buf.extend(type_.as_bytes()),
+            #[cfg(feature = "cbor")]
+            Kind::Cbor(_) => buf.extend(CBOR_TYPE.as_bytes()),
+        };
+        // ID
+        if let Some(id) = self.id {
+            buf = buf.extend(id);
+        }
+        // Payload
+        match self.kind {
+            Kind::Text { enc, txt } => buf
+                .push(enc.len() as u8)
+                .extend(enc.as_bytes())
+                .extend(txt.as_bytes()),
+            Kind::Uri { prefix, uri } => buf.push(prefix).extend(uri),
+            Kind::External { data, .. } | Kind::Media { data, .. } => buf.extend(data),
+            #[cfg(feature = "cbor")]
+            Kind::Cbor(data) => buf.extend(data),
+        }
+    }
+
+    /// Length of the message made of `records`, the `N` of
+    /// [`ConstRecord::message`]. Two records carrying the same ID are refused
+    /// here, as [`crate::Message::validate`] would.
+    pub const fn message_len(records: &[ConstRecord]) -> usize {
+        if records.is_empty() {
+            panic!("the message holds no record");
+        }
+        let mut len = 0;
+        let mut i = 0;
+        while i < records.len() {
+            if let Some(id) = records[i].id {
+                let mut j = 0;
+                while j < i {
+                    if let Some(other) = records[j].id {
+                        if bytes_eq(id, other) {
+                            panic!("ID is carried by another record");
+                        }
+                    }
+                    j += 1;
+                }
+            }
+            len += records[i].len();
+            i += 1;
+        }
+        len
+    }
+
+    /// The message made of `records`, `N` being [`ConstRecord::message_len`].
+    pub const fn message<const N: usize>(records: &[ConstRecord]) -> [u8; N] {
+        let mut buf = Writer {
+            buf: [0; N],
+            len: 0,
+        };
+        let mut i = 0;
+        while i < records.len() {
+            buf = records[i].write(buf, i == 0, i + 1 == records.len());
+            i += 1;
+        }
+        if buf.len != N {
+            panic!("the message is shorter than its array");
+        }
+        buf.buf
+    }
+}
+
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConstRecord, Message};
+
+    /// The bytes are those the serializer writes for the same records.
+    #[test]
+    fn test_const_message() {
+        static MESSAGE: &[u8] = &crate::message![
+            ConstRecord::text("en", "hi").id(b"t"),
+            ConstRecord::uri("https://www.example.com"),
+            ConstRecord::external("ex.com", "t", &[0x61; 300]),
+            ConstRecord::media("text/plain", b"x"),
+        ];
+        let msg = Message::builder()
+            .text("en", "hi")
+            .id(b"t")
+            .uri("https://www.example.com")
+            .external("ex.com", "t", &[0x61; 300])
+            .media("text/plain", b"x")
+            .build()
+            .unwrap();
+        #[cfg(feature = "alloc")]
+        assert_eq!(msg.to_vec().unwrap(), MESSAGE);
+        // The array is longer than the fixed capacity of the serializer.
+        #[cfg(not(feature = "alloc"))]
+        assert_eq!(msg.to_vec(), Err(crate::Error::BufferTooSmall));
+        assert_eq!(Message::try_from(MESSAGE).unwrap(), msg);
+    }
+}
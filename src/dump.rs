@@ -0,0 +1,249 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use core::fmt;
+
+use crate::{Header, Message, Parsed, Payload, Record, RecordType, TypeNameFormat};
+
+/// Bytes of a payload shown by the dump.
+const PREVIEW_LEN: usize = 16;
+
+/// Bytes shown in hex, then as ASCII with a dot for anything not printable.
+/// Only the first [`PREVIEW_LEN`] are shown, followed by `...` if there are
+/// more.
+struct Preview<I>(I);
+
+impl<I: Iterator<Item = u8> + Clone> fmt::Display for Preview<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let more = self.0.clone().nth(PREVIEW_LEN).is_some();
+        for (i, byte) in self.0.clone().take(PREVIEW_LEN).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        if more {
+            f.write_str(" ...")?;
+        }
+        f.write_str(" |")?;
+        for byte in self.0.clone().take(PREVIEW_LEN) {
+            let c = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            };
+            write!(f, "{c}")?;
+        }
+        f.write_str("|")
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:02x}", self.0)?;
+        let flags = [
+            (self.message_begin(), "MB"),
+            (self.message_end(), "ME"),
+            (self.message_chunk(), "CF"),
+            (self.short_record(), "SR"),
+            (self.id_length(), "IL"),
+        ];
+        for (set, name) in flags {
+            write!(f, " {}", if set { name } else { "--" })?;
+        }
+        Ok(())
+    }
+}
+
+impl Payload<'_> {
+    /// Bytes of the payload as written, without writing them to a buffer.
+    fn bytes(&self) -> impl Iterator<Item = u8> + Clone + '_ {
+        let (first, head, tail): (Option<u8>, &[u8], &[u8]) = match self {
+            Payload::RTD(RecordType::Text { enc, txt }) => {
+                (Some(enc.len() as u8), enc.as_bytes(), txt.as_bytes())
+            }
+            Payload::RTD(RecordType::Uri { prefix, uri }) => (Some(*prefix), uri.as_bytes(), &[]),
+            Payload::RTD(RecordType::External { data, .. } | RecordType::SmartPoster(data))
+            | Payload::Media { data, .. }
+            | Payload::AbsoluteUri { data, .. }
+            | Payload::Unknown(data) => (None, data, &[]),
+            Payload::Empty => (None, &[], &[]),
+            #[cfg(feature = "cbor")]
+            Payload::RTD(RecordType::Cbor(data)) => (None, data, &[]),
+        };
+        first
+            .into_iter()
+            .chain(head.iter().copied())
+            .chain(tail.iter().copied())
+    }
+}
+
+/// Writes the first line of the dump of a message of `count` records.
+fn dump_message(f: &mut fmt::Formatter<'_>, count: usize) -> fmt::Result {
+    let records = if count == 1 { "record" } else { "records" };
+    write!(f, "NDEF message, {count} {records}")
+}
+
+impl Record<'_> {
+    /// Writes the dump of the record, which is record `index` of a message,
+    /// starts at byte `offset` with `header` and is split into `chunks`.
+    fn dump(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        index: usize,
+        offset: usize,
+        header: Header,
+        chunks: usize,
+    ) -> fmt::Result {
+        writeln!(f, "record {index} at byte {offset}")?;
+        match self.chunk_size() {
+            Some(size) if chunks > 1 => {
+                writeln!(f, "  header   {header}, {chunks} chunks of {size} bytes")?;
+            }
+            _ => writeln!(f, "  header   {header}")?,
+        }
+        let tnf = TypeNameFormat::from(&self.payload);
+        writeln!(f, "  TNF      {tnf:?} ({})", tnf.code())?;
+        match self.get_type() {
+            Ok(type_name) => writeln!(f, "  type     {type_name}")?,
+            Err(error) => writeln!(f, "  type     <{error}>")?,
+        }
+        if let Some(id) = self.id() {
+            writeln!(f, "  ID       {}", Preview(id.iter().copied()))?;
+        }
+        writeln!(f, "  payload  {} bytes", self.payload.len())?;
+        match &self.payload {
+            Payload::RTD(RecordType::Text { enc, txt }) => {
+                writeln!(f, "  text     [{enc}] {txt}")?;
+            }
+            Payload::RTD(RecordType::Uri { prefix, uri }) => {
+                writeln!(f, "  URI      {}{uri}", RecordType::uri_prefix(*prefix))?;
+            }
+            _ => {}
+        }
+        writeln!(f, "  preview  {}", Preview(self.payload.bytes()))
+    }
+}
+
+/// Structured dump of the message, one block per record with its offset,
+/// header flags, type, ID, decoded content and a preview of its payload. It
+/// needs no allocation, so it can be written straight to a debug console.
+///
+/// Lengths, offsets and flags are those of the message as
+/// [`Message::to_vec`] writes it; the dump of [`Parsed`] shows those the
+/// message was read with.
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let length: usize = self.records.iter().map(Record::encoded_len).sum();
+        let count = self.records.len();
+        dump_message(f, count)?;
+        writeln!(f, ", written in {length} bytes")?;
+        let mut offset = 0;
+        for (index, record) in self.records.iter().enumerate() {
+            let header = record.header(index, count);
+            let (header, chunks) = match record.chunked_size() {
+                Some(size) => (
+                    header.chunk(true, false, size),
+                    record.payload.len().div_ceil(size),
+                ),
+                None => (header, 1),
+            };
+            record.dump(f, index, offset, header, chunks)?;
+            offset += record.encoded_len();
+        }
+        Ok(())
+    }
+}
+
+/// Dump of the parsed message in the form of that of [`Message`], with the
+/// offset, header and chunks of each record as they were read.
+impl fmt::Display for Parsed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        dump_message(f, self.message.records.len())?;
+        writeln!(f, " as read")?;
+        let records = self.message.records.iter().zip(&self.layout);
+        for (index, (record, layout)) in records.enumerate() {
+            let header = Header(layout.header);
+            record.dump(f, index, layout.offset, header, layout.chunks)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+    use crate::{Mode, ParseOptions};
+
+    #[test]
+    fn test_dump() {
+        let msg = Message::builder()
+            .text("en", "hi")
+            .id(b"t\x01")
+            .uri("https://www.example.com/index")
+            .build()
+            .unwrap();
+        let mut dump = heapless::String::<1024>::new();
+        write!(dump, "{msg}").unwrap();
+        let expected = "\
+NDEF message, 2 records, written in 34 bytes
+record 0 at byte 0
+  header   0x99 MB -- -- SR IL
+  TNF      NfcWellKnown (1)
+  type     T
+  ID       74 01 |t.|
+  payload  5 bytes
+  text     [en] hi
+  preview  02 65 6e 68 69 |.enhi|
+record 1 at byte 12
+  header   0x51 -- ME -- SR --
+  TNF      NfcWellKnown (1)
+  type     U
+  payload  18 bytes
+  URI      https://www.example.com/index
+  preview  02 65 78 61 6d 70 6c 65 2e 63 6f 6d 2f 69 6e 64 ... |.example.com/ind|
+";
+        assert_eq!(dump, expected);
+        assert_eq!(msg.to_vec().unwrap().len(), 34);
+
+        let msg = Message::builder()
+            .media("text/plain", b"abcde")
+            .chunk_size(core::num::NonZeroUsize::new(2).unwrap())
+            .build()
+            .unwrap();
+        dump.clear();
+        write!(dump, "{msg}").unwrap();
+        assert!(dump.contains("  header   0xb2 MB -- CF SR --, 3 chunks of 2 bytes\n"));
+        assert!(dump.starts_with("NDEF message, 1 record, written in 24 bytes\n"));
+        assert_eq!(msg.to_vec().unwrap().len(), 24);
+
+        // a record read in its normal form is shown as it is written
+        let raw = [0xC1, 0x01, 0x00, 0x00, 0x00, 0x01, b'U', 0x00];
+        let msg = Message::try_from(raw.as_slice()).unwrap();
+        dump.clear();
+        write!(dump, "{msg}").unwrap();
+        assert!(dump.starts_with("NDEF message, 1 record, written in 5 bytes\n"));
+        assert!(dump.contains("  header   0xd1 MB ME -- SR --\n"));
+    }
+
+    /// A parsed message is shown as it was read, missing flags included.
+    #[test]
+    fn test_dump_parsed() {
+        // a record in its normal form, then one that does not end the message
+        let raw = [
+            0x81, 0x01, 0x00, 0x00, 0x00, 0x01, b'U', 0x00, 0x11, 0x01, 0x01, b'U', 0x00,
+        ];
+        let parsed = Message::parse(&raw, &ParseOptions::new(Mode::Lenient)).unwrap();
+        let mut dump = heapless::String::<1024>::new();
+        write!(dump, "{parsed}").unwrap();
+        assert!(dump.starts_with("NDEF message, 2 records as read\nrecord 0 at byte 0\n"));
+        assert!(dump.contains("  header   0x81 MB -- -- -- --\n"));
+        assert!(dump.contains("record 1 at byte 8\n  header   0x11 -- -- -- SR --\n"));
+
+        dump.clear();
+        write!(dump, "{}", parsed.message).unwrap();
+        assert!(dump.contains("record 1 at byte 5\n  header   0x51 -- ME -- SR --\n"));
+    }
+}
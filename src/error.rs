@@ -1,13 +1,14 @@
 // SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use core::fmt;
+
 use derive_more::From;
 
-#[derive(Debug, From, PartialEq)]
+#[derive(Clone, Debug, From, PartialEq)]
 #[non_exhaustive]
-pub enum Error<'a> {
-    #[cfg(not(feature = "alloc"))]
-    /// The destination buffer is too small
+pub enum Error {
+    /// The destination buffer or message is full
     BufferTooSmall,
     /// The provided slice is too short
     SliceTooShort,
@@ -23,21 +24,61 @@ pub enum Error<'a> {
     InvalidTextStatus,
     /// A record does not begin or end the message where the format requires it
     InvalidFraming,
+    /// Two records of the message carry the same ID
+    DuplicateId,
+    /// No record of the message carries the ID passed to
+    /// [`Message::resolve`](crate::Message::resolve)
+    UnresolvedId,
+    /// A TLV of a tag data area has an invalid length, or describes more
+    /// reserved areas than the crate keeps track of
+    InvalidTlv,
+    /// The tag data area holds no NDEF Message TLV
+    MissingNdefTlv,
+    /// The capability container of the tag does not announce an NDEF mapping
+    /// this crate supports, or denies reading the tag
+    InvalidCapabilityContainer,
+    /// The address is outside the tag memory
+    InvalidAddress,
+    /// The tag memory is locked against writing
+    ReadOnly,
+    /// The checksum of a block does not match its bytes
+    InvalidChecksum,
+    /// The tag was left in the middle of a write of its NDEF message
+    WriteInProgress,
+    /// The tag answered a command with a status word other than 90 00
+    UnexpectedStatus(u16),
     /// The record is a chunk of a larger payload, which is not supported
+    /// without `alloc`
     UnsupportedChunkedRecord,
+    /// A chunk does not continue the chunked payload before it, or the message
+    /// ends before its last chunk
+    InvalidChunk,
+    /// The field must be empty under the type name format of the record
+    NonEmptyField(Field),
     /// The type name format is not supported yet (to be implemented)
     UnsupportedTypeNameFormat(crate::TypeNameFormat),
     /// The provided external type does not contain a ':'
-    InvalidExternalType(&'a str),
+    InvalidExternalType(Name),
+    /// The provided media type is not a type and subtype separated by a '/'
+    InvalidMediaType(Name),
     /// The record type is not supported yet (to be implemented)
-    UnsupportedRecordType(&'a str),
+    UnsupportedRecordType(Name),
+    /// The data given for a record does not suit its record type, such as a
+    /// Web NFC URL record whose data is not text
+    InvalidRecordData,
+    /// The message holds more records than [`crate::Limits::records`], or
+    /// than a message holds without `alloc`
+    TooManyRecords,
+    /// A payload is longer than [`crate::Limits::payload`]
+    PayloadTooLarge,
+    /// The payloads of the message are longer than
+    /// [`crate::Limits::total_payload`]
+    TotalPayloadTooLarge,
+    /// A payload is split into more chunks than [`crate::Limits::chunks`]
+    TooManyChunks,
     /// The provided data is not valid UTF-8
     #[from]
     UTF8(core::str::Utf8Error),
-    /// The provided data is not valid UTF-8
-    #[from]
-    #[cfg(feature = "alloc")]
-    UTF8Decode(alloc::string::FromUtf8Error),
     /// The provided data is not valid UTF-16
     #[cfg(feature = "alloc")]
     UTF16Decode,
@@ -46,14 +87,222 @@ pub enum Error<'a> {
     UTF16OddLength(usize),
 }
 
-pub type Result<'a, T> = core::result::Result<T, Error<'a>>;
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Longest name an [`Error`] quotes in full.
+const NAME_LEN: usize = 32;
+
+/// Name quoted by an [`Error`]. It is copied out of the input so that the
+/// error does not borrow it, and cut on a character boundary when longer than
+/// 32 bytes.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Name {
+    name: heapless::String<NAME_LEN>,
+    truncated: bool,
+}
+
+impl Name {
+    /// The quoted name, possibly truncated.
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the name was too long to be quoted in full.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        let mut quoted = Self::default();
+        for c in name.chars() {
+            if quoted.name.push(c).is_err() {
+                quoted.truncated = true;
+                break;
+            }
+        }
+        quoted
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())?;
+        if self.truncated {
+            f.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if self.truncated {
+            f.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+/// Field of a record, in wire order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Field {
+    Header,
+    TypeLength,
+    PayloadLength,
+    IdLength,
+    Type,
+    Id,
+    Payload,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Field::Header => "header",
+            Field::TypeLength => "type length",
+            Field::PayloadLength => "payload length",
+            Field::IdLength => "ID length",
+            Field::Type => "type",
+            Field::Id => "ID",
+            Field::Payload => "payload",
+        })
+    }
+}
+
+/// An [`Error`] found while parsing a message, with the place it was found at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    error: Error,
+    offset: usize,
+    record: usize,
+    field: Field,
+}
+
+impl ParseError {
+    pub(crate) fn new(error: Error, offset: usize, record: usize, field: Field) -> Self {
+        Self {
+            error,
+            offset,
+            record,
+            field,
+        }
+    }
+
+    /// What is wrong with the input.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Offset in the parsed slice of the byte the error was detected at. For a
+    /// field too short to be read, it is where the field starts.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Index of the record the error was detected in.
+    pub fn record(&self) -> usize {
+        self.record
+    }
+
+    /// Field of the record the error was detected in.
+    pub fn field(&self) -> Field {
+        self.field
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        error.error
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BufferTooSmall => f.write_str("buffer or message is full"),
+            Error::SliceTooShort => f.write_str("input ends before the field it announces"),
+            Error::UnsupportedEncoding => {
+                f.write_str("text encoding is not supported in this configuration")
+            }
+            Error::FieldTooLong => f.write_str("field is longer than its length can announce"),
+            Error::EmptyMessage => f.write_str("message holds no record"),
+            Error::InvalidLanguageCode => {
+                f.write_str("language code is empty, too long or not US-ASCII")
+            }
+            Error::InvalidTextStatus => f.write_str("text status byte has its reserved bit set"),
+            Error::InvalidFraming => {
+                f.write_str("message begin or end flag contradicts the record position")
+            }
+            Error::DuplicateId => f.write_str("ID is carried by another record"),
+            Error::UnresolvedId => f.write_str("no record carries the referenced ID"),
+            Error::InvalidTlv => f.write_str("TLV of the tag data area is invalid"),
+            Error::MissingNdefTlv => f.write_str("tag data area holds no NDEF message TLV"),
+            Error::InvalidCapabilityContainer => {
+                f.write_str("capability container does not allow reading NDEF")
+            }
+            Error::InvalidAddress => f.write_str("address is outside the tag memory"),
+            Error::ReadOnly => f.write_str("tag memory is read-only"),
+            Error::InvalidChecksum => f.write_str("checksum does not match the block"),
+            Error::WriteInProgress => f.write_str("tag is in the middle of a write"),
+            Error::UnexpectedStatus(sw) => write!(f, "tag answered with status word {sw:04X}"),
+            Error::UnsupportedChunkedRecord => f.write_str("chunked records are not supported"),
+            Error::InvalidChunk => f.write_str("chunk does not continue a chunked payload"),
+            Error::NonEmptyField(field) => {
+                write!(f, "{field} must be empty under this type name format")
+            }
+            Error::UnsupportedTypeNameFormat(tnf) => {
+                write!(f, "type name format {tnf:?} is not supported")
+            }
+            Error::InvalidExternalType(name) => write!(f, "invalid external type name `{name}`"),
+            Error::InvalidMediaType(name) => write!(f, "invalid media type `{name}`"),
+            Error::UnsupportedRecordType(name) => {
+                write!(f, "record type `{name}` is not supported")
+            }
+            Error::InvalidRecordData => f.write_str("data does not suit the record type"),
+            Error::TooManyRecords => f.write_str("message holds too many records"),
+            Error::PayloadTooLarge => f.write_str("payload is too large"),
+            Error::TotalPayloadTooLarge => f.write_str("payloads of the message are too large"),
+            Error::TooManyChunks => f.write_str("payload is split into too many chunks"),
+            Error::UTF8(_) => f.write_str("text is not valid UTF-8"),
+            #[cfg(feature = "alloc")]
+            Error::UTF16Decode => f.write_str("text is not valid UTF-16"),
+            #[cfg(feature = "alloc")]
+            Error::UTF16OddLength(len) => {
+                write!(f, "UTF-16 text has an odd length of {len} bytes")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (record {}, {} at byte {})",
+            self.error, self.record, self.field, self.offset
+        )
+    }
+}
 
 #[rustversion::since(1.81)]
-impl<'a> core::error::Error for Error<'a> {}
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::UTF8(error) => Some(error),
+            _ => None,
+        }
+    }
+}
 
+/// The message of the inner error is part of the message of a
+/// [`ParseError`], so its source is the one of the inner error.
 #[rustversion::since(1.81)]
-impl<'a> core::fmt::Display for Error<'a> {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "{self:?}")
+impl core::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.error.source()
     }
 }
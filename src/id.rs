@@ -0,0 +1,146 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use core::fmt::Write;
+#[cfg(not(feature = "alloc"))]
+use core::ops::Deref;
+
+#[cfg(feature = "alloc")]
+use crate::Bytes;
+use crate::{Error, Message, Record, Result};
+
+/// ID generated by [`Message::unique_id`]: a decimal number, which keeps it
+/// printable and short.
+pub type RecordId = heapless::String<20>;
+
+/// ID field of a record. With `alloc` it is [`Bytes`]; without it, a
+/// generated ID is held in the record, as nothing else would outlive it.
+#[cfg(feature = "alloc")]
+pub(crate) type IdField<'a> = Bytes<'a>;
+#[cfg(not(feature = "alloc"))]
+#[derive(Clone, Debug)]
+pub(crate) enum IdField<'a> {
+    Borrowed(&'a [u8]),
+    Generated(RecordId),
+}
+
+#[cfg(not(feature = "alloc"))]
+impl<'a> From<&'a [u8]> for IdField<'a> {
+    fn from(id: &'a [u8]) -> Self {
+        IdField::Borrowed(id)
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl Deref for IdField<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            IdField::Borrowed(id) => id,
+            IdField::Generated(id) => id.as_bytes(),
+        }
+    }
+}
+
+/// A generated ID compares equal to the same bytes borrowed.
+#[cfg(not(feature = "alloc"))]
+impl PartialEq for IdField<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+/// ID field holding the generated `id`.
+pub(crate) fn generated(id: RecordId) -> IdField<'static> {
+    #[cfg(feature = "alloc")]
+    return Bytes::Owned(id.as_bytes().to_vec());
+    #[cfg(not(feature = "alloc"))]
+    IdField::Generated(id)
+}
+
+impl<'a> Message<'a> {
+    /// Index and record carrying `id`. IDs are compared byte for byte.
+    pub fn find_id(&self, id: &[u8]) -> Option<(usize, &Record<'a>)> {
+        self.records
+            .iter()
+            .enumerate()
+            .find(|(_, record)| record.id() == Some(id))
+    }
+
+    /// Record another record refers to by `id`, refused with
+    /// [`Error::UnresolvedId`] when no record carries it and with
+    /// [`Error::DuplicateId`] when several do. This is the resolution a record
+    /// type referring to another by ID is to go through; the crate has no such
+    /// type yet.
+    pub fn resolve(&self, id: &[u8]) -> Result<&Record<'a>> {
+        let (index, record) = self.find_id(id).ok_or(Error::UnresolvedId)?;
+        if self.records[index + 1..]
+            .iter()
+            .any(|other| other.id() == Some(id))
+        {
+            return Err(Error::DuplicateId);
+        }
+        Ok(record)
+    }
+
+    /// Indices of the records whose ID is already carried by an earlier
+    /// record.
+    pub fn duplicate_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.records
+            .iter()
+            .enumerate()
+            .filter_map(|(index, record)| {
+                let id = record.id()?;
+                self.records[..index]
+                    .iter()
+                    .any(|other| other.id() == Some(id))
+                    .then_some(index)
+            })
+    }
+
+    /// Smallest decimal number not yet used as an ID by the message. One of
+    /// the numbers up to the record count plus one is always free.
+    ///
+    /// [`MessageBuilder::unique_id`](crate::MessageBuilder::unique_id) gives
+    /// one to a record being built.
+    pub fn unique_id(&self) -> RecordId {
+        let mut id = RecordId::new();
+        for n in 1.. {
+            id.clear();
+            // 20 digits hold any `usize`.
+            let _ = write!(id, "{n}");
+            if self.find_id(id.as_bytes()).is_none() {
+                break;
+            }
+        }
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Payload, RecordType};
+
+    #[test]
+    fn test_record_ids() {
+        let text = || Payload::RTD(RecordType::text("en", "x"));
+        let mut msg = Message::default();
+        assert_eq!(msg.unique_id(), "1");
+        msg.append_record(Record::new(Some(b"1"), text())).unwrap();
+        msg.append_record(Record::new(None, text())).unwrap();
+        msg.append_record(Record::new(Some(b"a"), text())).unwrap();
+        assert_eq!(msg.unique_id(), "2");
+
+        assert_eq!(msg.find_id(b"a").map(|(index, _)| index), Some(2));
+        assert_eq!(msg.find_id(b"A"), None);
+        assert_eq!(msg.resolve(b"1").unwrap().id(), Some(b"1".as_slice()));
+        assert_eq!(msg.resolve(b"2").unwrap_err(), Error::UnresolvedId);
+        assert_eq!(msg.duplicate_ids().next(), None);
+
+        msg.append_record(Record::new(Some(b"a"), text())).unwrap();
+        assert_eq!(msg.resolve(b"a").unwrap_err(), Error::DuplicateId);
+        assert!(msg.duplicate_ids().eq([3]));
+    }
+}
@@ -3,17 +3,63 @@
 
 #![no_std]
 
+mod builder;
+mod constant;
+mod dump;
 mod error;
-
-pub use error::{Error, Result};
+mod id;
+#[cfg(feature = "defmt")]
+mod log;
+mod parse;
+mod semantic;
+#[cfg(feature = "serde")]
+mod serialization;
+mod validate;
+#[cfg(feature = "serde")]
+mod web_nfc;
+
+pub mod mifare_classic;
+pub mod type2;
+pub mod type3;
+pub mod type4;
+pub mod type5;
+
+pub use builder::MessageBuilder;
+pub use constant::ConstRecord;
+pub use error::{Error, Field, Name, ParseError, Result};
+pub use id::RecordId;
+pub use parse::{
+    Check, Diagnostic, Diagnostics, Layouts, Limits, Mode, ParseOptions, Parsed, RecordLayout,
+};
+pub use validate::{Issue, Issues};
+#[cfg(feature = "serde")]
+pub use web_nfc::{NdefData, NdefMessageInit, NdefRecordInit};
 
 #[cfg(feature = "alloc")]
 extern crate alloc;
 #[cfg(feature = "alloc")]
-use alloc::{format, string::String, vec::Vec};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    vec::Vec,
+};
+use core::num::NonZeroUsize;
 #[cfg(not(feature = "alloc"))]
 use heapless::Vec;
 
+/// Text held by a record. Without `alloc` it always borrows from the caller or
+/// the parsed input; with `alloc` it may also be owned, which is what lets
+/// [`Message::into_owned`] detach a message from its input.
+#[cfg(feature = "alloc")]
+pub type Str<'a> = Cow<'a, str>;
+#[cfg(not(feature = "alloc"))]
+pub type Str<'a> = &'a str;
+
+/// Bytes held by a record, borrowed or owned like [`Str`].
+#[cfg(feature = "alloc")]
+pub type Bytes<'a> = Cow<'a, [u8]>;
+#[cfg(not(feature = "alloc"))]
+pub type Bytes<'a> = &'a [u8];
+
 /// Wire type name of the CBOR external record.
 #[cfg(feature = "cbor")]
 const CBOR_TYPE: &str = "cbor.io:cbor";
@@ -33,6 +79,47 @@ const TEXT_LANGUAGE_LEN_MASK: u8 = 0x3f;
 const TEXT_RESERVED_MASK: u8 = 0x40;
 const TEXT_UTF16_MASK: u8 = 0x80;
 
+/// Prefixes a URI record abbreviates, indexed by their identifier code. Codes
+/// past the end of the table are reserved and abbreviate nothing.
+const URI_PREFIXES: [&str; 36] = [
+    "",
+    "http://www.",
+    "https://www.",
+    "http://",
+    "https://",
+    "tel:",
+    "mailto:",
+    "ftp://anonymous:anonymous@",
+    "ftp://ftp.",
+    "ftps://",
+    "sftp://",
+    "smb://",
+    "nfs://",
+    "ftp://",
+    "dav://",
+    "news:",
+    "telnet://",
+    "imap:",
+    "rtsp://",
+    "urn:",
+    "pop:",
+    "sip:",
+    "sips:",
+    "tftp:",
+    "btspp://",
+    "btl2cap://",
+    "btgoep://",
+    "tcpobex://",
+    "irdaobex://",
+    "file://",
+    "urn:epc:id:",
+    "urn:epc:tag:",
+    "urn:epc:pat:",
+    "urn:epc:raw:",
+    "urn:epc:",
+    "urn:nfc:",
+];
+
 /// Buffer holding serialized bytes. Without `alloc` the capacity is fixed and
 /// the serializer reports [`Error::BufferTooSmall`] once it is exhausted.
 #[cfg(feature = "alloc")]
@@ -40,18 +127,31 @@ pub type Buffer = Vec<u8>;
 #[cfg(not(feature = "alloc"))]
 pub type Buffer = Vec<u8, 256>;
 
+/// Borrows `value` as a record field, see [`Str`] and [`Bytes`].
 #[cfg(feature = "alloc")]
-fn write_all<'a>(buf: &mut Buffer, data: &[u8]) -> Result<'a, ()> {
+fn borrowed<T: ?Sized + ToOwned>(value: &T) -> Cow<'_, T> {
+    Cow::Borrowed(value)
+}
+#[cfg(not(feature = "alloc"))]
+fn borrowed<T: ?Sized>(value: &T) -> &T {
+    value
+}
+
+/// Wire type name of a record, see [`Record::get_type`].
+pub type TypeName = heapless::String<MAX_FIELD_LEN>;
+
+#[cfg(feature = "alloc")]
+fn write_all(buf: &mut Buffer, data: &[u8]) -> Result<()> {
     buf.extend_from_slice(data);
     Ok(())
 }
 #[cfg(not(feature = "alloc"))]
-fn write_all<'a>(buf: &mut Buffer, data: &[u8]) -> Result<'a, ()> {
+fn write_all(buf: &mut Buffer, data: &[u8]) -> Result<()> {
     buf.extend_from_slice(data)
         .map_err(|_| Error::BufferTooSmall)
 }
 
-fn write_u8<'a>(buf: &mut Buffer, byte: u8) -> Result<'a, ()> {
+fn write_u8(buf: &mut Buffer, byte: u8) -> Result<()> {
     write_all(buf, &[byte])
 }
 
@@ -59,65 +159,229 @@ fn write_u8<'a>(buf: &mut Buffer, byte: u8) -> Result<'a, ()> {
 /// are US-ASCII and neither may be empty; the colon separating them cannot
 /// appear again inside the type name. Case is preserved as given, while the
 /// format compares external type names case insensitively.
-fn check_external_type<'a>(domain: &'a str, type_: &'a str) -> Result<'a, ()> {
-    let domain_byte =
-        |byte: u8| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'.' || byte == b'_';
-    if domain.is_empty() || !domain.bytes().all(domain_byte) {
-        return Err(Error::InvalidExternalType(domain));
+fn check_external_type(domain: &str, type_: &str) -> Result<()> {
+    if !is_external_domain(domain.as_bytes()) {
+        return Err(Error::InvalidExternalType(domain.into()));
+    }
+    if !is_external_type(type_.as_bytes()) {
+        return Err(Error::InvalidExternalType(type_.into()));
+    }
+    Ok(())
+}
+
+// The checks are `const` so that `message!` runs them at compile time, which
+// rules out iterators and closures.
+const fn is_external_domain(domain: &[u8]) -> bool {
+    let mut i = 0;
+    while i < domain.len() {
+        let byte = domain[i];
+        if !(byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'.' || byte == b'_') {
+            return false;
+        }
+        i += 1;
+    }
+    !domain.is_empty()
+}
+
+const fn is_external_type(type_: &[u8]) -> bool {
+    let mut i = 0;
+    while i < type_.len() {
+        if !type_[i].is_ascii_graphic() || type_[i] == b':' {
+            return false;
+        }
+        i += 1;
+    }
+    !type_.is_empty()
+}
+
+/// The language code of a Text record is announced by six bits of its status
+/// byte and, being an IANA language tag, is US-ASCII.
+fn check_language(enc: &str) -> Result<()> {
+    if !is_language(enc.as_bytes()) {
+        return Err(Error::InvalidLanguageCode);
+    }
+    Ok(())
+}
+
+const fn is_language(enc: &[u8]) -> bool {
+    let mut i = 0;
+    while i < enc.len() {
+        if !enc[i].is_ascii() {
+            return false;
+        }
+        i += 1;
     }
-    let type_byte = |byte: u8| byte.is_ascii_graphic() && byte != b':';
-    if type_.is_empty() || !type_.bytes().all(type_byte) {
-        return Err(Error::InvalidExternalType(type_));
+    !enc.is_empty() && enc.len() <= MAX_LANGUAGE_LEN
+}
+
+/// A media type is a type and a subtype separated by a slash, both being
+/// RFC 2045 tokens, and may be followed by parameters after a semicolon.
+fn check_media_type(media_type: &str) -> Result<()> {
+    if !is_media_type(media_type.as_bytes()) {
+        return Err(Error::InvalidMediaType(media_type.into()));
     }
     Ok(())
 }
 
+const fn is_media_type(media_type: &[u8]) -> bool {
+    const fn is_token(byte: u8) -> bool {
+        byte.is_ascii_graphic()
+            && !matches!(
+                byte,
+                b'(' | b')'
+                    | b'<'
+                    | b'>'
+                    | b'@'
+                    | b','
+                    | b';'
+                    | b':'
+                    | b'\\'
+                    | b'"'
+                    | b'/'
+                    | b'['
+                    | b']'
+                    | b'?'
+                    | b'='
+            )
+    }
+    // The type, the subtype, then the parameters.
+    let mut part = 0;
+    let mut part_len = 0;
+    let mut i = 0;
+    while i < media_type.len() {
+        let byte = media_type[i];
+        i += 1;
+        if part == 2 {
+            if !byte.is_ascii_graphic() && byte != b' ' {
+                return false;
+            }
+        } else if (part == 0 && byte == b'/') || (part == 1 && byte == b';') {
+            if part_len == 0 {
+                return false;
+            }
+            part += 1;
+            part_len = 0;
+        } else if is_token(byte) {
+            part_len += 1;
+        } else {
+            return false;
+        }
+    }
+    part == 2 || (part == 1 && part_len > 0)
+}
+
+/// Identifier code of the longest prefix of `uri` a URI record abbreviates.
+const fn uri_prefix_code(uri: &[u8]) -> usize {
+    let mut prefix = 0;
+    let mut code = 1;
+    while code < URI_PREFIXES.len() {
+        let abbreviated = URI_PREFIXES[code].as_bytes();
+        if abbreviated.len() > URI_PREFIXES[prefix].len() && abbreviated.len() <= uri.len() {
+            let mut i = 0;
+            while i < abbreviated.len() && uri[i] == abbreviated[i] {
+                i += 1;
+            }
+            if i == abbreviated.len() {
+                prefix = code;
+            }
+        }
+        code += 1;
+    }
+    prefix
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 pub enum TypeNameFormat {
     Empty,
+    #[cfg_attr(feature = "serde", serde(rename = "well-known"))]
     NfcWellKnown,
     Media,
     AbsoluteUri,
+    #[cfg_attr(feature = "serde", serde(rename = "external"))]
     NfcExternal,
     Unknown,
     Unchanged,
     Reserved,
 }
 
+impl TypeNameFormat {
+    /// Whether `a` and `b` name the same type in this format. Well-known
+    /// types compare case sensitively. External types compare case
+    /// insensitively, and so do the type and subtype of a media type, its
+    /// parameters comparing as written. Other names compare as written.
+    pub fn type_eq(&self, a: &str, b: &str) -> bool {
+        match self {
+            TypeNameFormat::NfcExternal => a.eq_ignore_ascii_case(b),
+            TypeNameFormat::Media => {
+                let (a, a_parameters) = a.split_once(';').unwrap_or((a, ""));
+                let (b, b_parameters) = b.split_once(';').unwrap_or((b, ""));
+                a.eq_ignore_ascii_case(b) && a_parameters == b_parameters
+            }
+            _ => a == b,
+        }
+    }
+
+    /// Value of the TNF field of the record header.
+    const fn code(&self) -> u8 {
+        match self {
+            TypeNameFormat::Empty => 0x00,
+            TypeNameFormat::NfcWellKnown => 0x01,
+            TypeNameFormat::Media => 0x02,
+            TypeNameFormat::AbsoluteUri => 0x03,
+            TypeNameFormat::NfcExternal => 0x04,
+            TypeNameFormat::Unknown => 0x05,
+            TypeNameFormat::Unchanged => 0x06,
+            TypeNameFormat::Reserved => 0x07,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq)]
 struct Header(u8);
 
 impl Header {
+    const MESSAGE_BEGIN: u8 = 0x80;
+    const MESSAGE_END: u8 = 0x40;
+    const MESSAGE_CHUNK: u8 = 0x20;
+    const SHORT_RECORD: u8 = 0x10;
+    const ID_LENGTH: u8 = 0x08;
+
     fn message_begin(&self) -> bool {
-        self.0 & 0x80 == 0x80
+        self.0 & Self::MESSAGE_BEGIN != 0
     }
     fn set_message_begin(&mut self) {
-        self.0 |= 0x80;
+        self.0 |= Self::MESSAGE_BEGIN;
     }
 
     fn message_end(&self) -> bool {
-        self.0 & 0x40 == 0x40
+        self.0 & Self::MESSAGE_END != 0
     }
     fn set_message_end(&mut self) {
-        self.0 |= 0x40;
+        self.0 |= Self::MESSAGE_END;
     }
 
     fn message_chunk(&self) -> bool {
-        self.0 & 0x20 == 0x20
+        self.0 & Self::MESSAGE_CHUNK != 0
+    }
+    fn set_message_chunk(&mut self) {
+        self.0 |= Self::MESSAGE_CHUNK;
     }
 
     fn short_record(&self) -> bool {
-        self.0 & 0x10 == 0x10
+        self.0 & Self::SHORT_RECORD != 0
     }
     fn set_short_record(&mut self) {
-        self.0 |= 0x10;
+        self.0 |= Self::SHORT_RECORD;
     }
 
     fn id_length(&self) -> bool {
-        self.0 & 0x08 == 0x08
+        self.0 & Self::ID_LENGTH != 0
     }
     fn set_id_length(&mut self) {
-        self.0 |= 0x08;
+        self.0 |= Self::ID_LENGTH;
     }
 
     fn type_name_format(&self) -> TypeNameFormat {
@@ -135,58 +399,131 @@ impl Header {
     }
     fn set_type_name_format(&mut self, tnf: TypeNameFormat) {
         self.0 &= !0x07;
-        self.0 |= match tnf {
-            TypeNameFormat::Empty => 0x00,
-            TypeNameFormat::NfcWellKnown => 0x01,
-            TypeNameFormat::Media => 0x02,
-            TypeNameFormat::AbsoluteUri => 0x03,
-            TypeNameFormat::NfcExternal => 0x04,
-            TypeNameFormat::Unknown => 0x05,
-            TypeNameFormat::Unchanged => 0x06,
-            TypeNameFormat::Reserved => 0x07,
-        };
+        self.0 |= tnf.code();
+    }
+
+    /// Header of a chunk of `len` bytes of the record this header describes.
+    /// Only the initial chunk names the type and carries the ID, the
+    /// following ones are of the unchanged type.
+    fn chunk(&self, initial: bool, last: bool, len: usize) -> Header {
+        let mut header = Header::default();
+        if initial {
+            header.set_type_name_format(self.type_name_format());
+            if self.message_begin() {
+                header.set_message_begin();
+            }
+            if self.id_length() {
+                header.set_id_length();
+            }
+        } else {
+            header.set_type_name_format(TypeNameFormat::Unchanged);
+        }
+        if !last {
+            header.set_message_chunk();
+        } else if self.message_end() {
+            header.set_message_end();
+        }
+        if len <= MAX_SHORT_PAYLOAD_LEN {
+            header.set_short_record();
+        }
+        header
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum RecordType<'a> {
-    #[cfg(not(feature = "alloc"))]
-    Text { enc: &'a str, txt: &'a str },
-    #[cfg(feature = "alloc")]
-    Text { enc: &'a str, txt: String },
+    /// Text in a given language. UTF-8 text borrows from the parsed input,
+    /// only UTF-16 text is decoded into an owned string.
+    Text { enc: Str<'a>, txt: Str<'a> },
+    /// URI whose start is abbreviated by the identifier code `prefix`, see
+    /// [`RecordType::uri_prefix`], and `uri` is the rest.
+    Uri { prefix: u8, uri: Str<'a> },
     External {
-        domain: &'a str,
-        type_: &'a str,
-        data: &'a [u8],
+        domain: Str<'a>,
+        type_: Str<'a>,
+        data: Bytes<'a>,
     },
-    #[cfg(all(feature = "cbor", not(feature = "alloc")))]
-    Cbor(&'a [u8]),
-    #[cfg(all(feature = "cbor", feature = "alloc"))]
-    Cbor(Vec<u8>),
+    /// Smart Poster, whose payload is an NDEF message describing a URI. The
+    /// message is kept serialized, to be parsed with [`Message::try_from`].
+    SmartPoster(Bytes<'a>),
+    #[cfg(feature = "cbor")]
+    Cbor(Bytes<'a>),
 }
 
 impl<'a> RecordType<'a> {
+    /// Text record borrowing its language code and text.
+    pub fn text(enc: &'a str, txt: &'a str) -> Self {
+        RecordType::Text {
+            enc: borrowed(enc),
+            txt: borrowed(txt),
+        }
+    }
+
+    /// URI record borrowing `uri`, whose start is abbreviated by the longest
+    /// prefix that matches it.
+    pub fn uri(uri: &'a str) -> Self {
+        let prefix = uri_prefix_code(uri.as_bytes());
+        RecordType::Uri {
+            prefix: prefix as u8,
+            uri: borrowed(&uri[URI_PREFIXES[prefix].len()..]),
+        }
+    }
+
+    /// Start of a URI abbreviated by the identifier code `prefix`. A reserved
+    /// code abbreviates nothing.
+    pub fn uri_prefix(prefix: u8) -> &'static str {
+        URI_PREFIXES
+            .get(prefix as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// External record borrowing its type name and data.
+    pub fn external(domain: &'a str, type_: &'a str, data: &'a [u8]) -> Self {
+        RecordType::External {
+            domain: borrowed(domain),
+            type_: borrowed(type_),
+            data: borrowed(data),
+        }
+    }
+
+    /// Smart Poster borrowing `ndef`, its serialized message.
+    pub fn smart_poster(ndef: &'a [u8]) -> Self {
+        RecordType::SmartPoster(borrowed(ndef))
+    }
+
+    /// CBOR record borrowing its encoded data.
+    #[cfg(feature = "cbor")]
+    pub fn cbor(data: &'a [u8]) -> Self {
+        RecordType::Cbor(borrowed(data))
+    }
+
     fn len(&self) -> usize {
         match self {
             RecordType::Text { enc, txt } => 1 + enc.len() + txt.len(),
-            RecordType::External { data, .. } => data.len(),
+            RecordType::Uri { uri, .. } => 1 + uri.len(),
+            RecordType::External { data, .. } | RecordType::SmartPoster(data) => data.len(),
             #[cfg(feature = "cbor")]
             RecordType::Cbor(data) => data.len(),
         }
     }
 
-    fn write(&self, buf: &mut Buffer) -> Result<'a, ()> {
+    fn write(&self, buf: &mut Buffer) -> Result<()> {
         match self {
             RecordType::Text { enc, txt } => {
-                if enc.is_empty() || enc.len() > MAX_LANGUAGE_LEN || !enc.is_ascii() {
-                    return Err(Error::InvalidLanguageCode);
-                }
+                check_language(enc)?;
                 // force utf-8 encoding here
                 write_u8(buf, enc.len() as u8)?;
                 write_all(buf, enc.as_bytes())?;
                 write_all(buf, txt.as_bytes())
             }
-            RecordType::External { data, .. } => write_all(buf, data),
+            RecordType::Uri { prefix, uri } => {
+                write_u8(buf, *prefix)?;
+                write_all(buf, uri.as_bytes())
+            }
+            RecordType::External { data, .. } | RecordType::SmartPoster(data) => {
+                write_all(buf, data)
+            }
             #[cfg(feature = "cbor")]
             RecordType::Cbor(data) => write_all(buf, data),
         }
@@ -195,31 +532,78 @@ impl<'a> RecordType<'a> {
     /// Length of the wire type name.
     fn type_len(&self) -> usize {
         match self {
-            RecordType::Text { .. } => 1,
+            RecordType::Text { .. } | RecordType::Uri { .. } => 1,
             RecordType::External { domain, type_, .. } => domain.len() + 1 + type_.len(),
+            RecordType::SmartPoster(_) => 2,
             #[cfg(feature = "cbor")]
             RecordType::Cbor(_) => CBOR_TYPE.len(),
         }
     }
 
-    fn write_type(&self, buf: &mut Buffer) -> Result<'a, ()> {
+    fn write_type(&self, buf: &mut Buffer) -> Result<()> {
         match self {
             RecordType::Text { .. } => write_all(buf, b"T"),
+            RecordType::Uri { .. } => write_all(buf, b"U"),
             RecordType::External { domain, type_, .. } => {
                 check_external_type(domain, type_)?;
                 write_all(buf, domain.as_bytes())?;
                 write_u8(buf, b':')?;
                 write_all(buf, type_.as_bytes())
             }
+            RecordType::SmartPoster(_) => write_all(buf, b"Sp"),
             #[cfg(feature = "cbor")]
             RecordType::Cbor(_) => write_all(buf, CBOR_TYPE.as_bytes()),
         }
     }
 }
 
+#[cfg(feature = "alloc")]
+impl RecordType<'_> {
+    /// Copies whatever the record still borrows, so it outlives its input.
+    pub fn into_owned(self) -> RecordType<'static> {
+        match self {
+            RecordType::Text { enc, txt } => RecordType::Text {
+                enc: Cow::Owned(enc.into_owned()),
+                txt: Cow::Owned(txt.into_owned()),
+            },
+            RecordType::Uri { prefix, uri } => RecordType::Uri {
+                prefix,
+                uri: Cow::Owned(uri.into_owned()),
+            },
+            RecordType::External {
+                domain,
+                type_,
+                data,
+            } => RecordType::External {
+                domain: Cow::Owned(domain.into_owned()),
+                type_: Cow::Owned(type_.into_owned()),
+                data: Cow::Owned(data.into_owned()),
+            },
+            RecordType::SmartPoster(data) => RecordType::SmartPoster(Cow::Owned(data.into_owned())),
+            #[cfg(feature = "cbor")]
+            RecordType::Cbor(data) => RecordType::Cbor(Cow::Owned(data.into_owned())),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Payload<'a> {
     RTD(RecordType<'a>),
+    /// Data typed by an RFC 2046 media type, such as `text/plain`.
+    Media {
+        type_: Str<'a>,
+        data: Bytes<'a>,
+    },
+    /// Data whose type is named by an absolute URI, as RFC 3986 defines it.
+    AbsoluteUri {
+        uri: Str<'a>,
+        data: Bytes<'a>,
+    },
+    /// No type and no payload, as in the single record of an otherwise empty
+    /// message. The record cannot carry an ID either.
+    Empty,
+    /// Data of an unknown type, which names no type.
+    Unknown(Bytes<'a>),
 }
 
 impl<'a> From<&Payload<'a>> for TypeNameFormat {
@@ -229,32 +613,97 @@ impl<'a> From<&Payload<'a>> for TypeNameFormat {
             #[cfg(feature = "cbor")]
             Payload::RTD(RecordType::Cbor(_)) => TypeNameFormat::NfcExternal,
             Payload::RTD(_) => TypeNameFormat::NfcWellKnown,
+            Payload::Media { .. } => TypeNameFormat::Media,
+            Payload::AbsoluteUri { .. } => TypeNameFormat::AbsoluteUri,
+            Payload::Empty => TypeNameFormat::Empty,
+            Payload::Unknown(_) => TypeNameFormat::Unknown,
         }
     }
 }
 
 impl<'a> Payload<'a> {
+    /// Media payload borrowing its type and data.
+    pub fn media(type_: &'a str, data: &'a [u8]) -> Self {
+        Payload::Media {
+            type_: borrowed(type_),
+            data: borrowed(data),
+        }
+    }
+
+    /// Payload borrowing its data and `uri`, the absolute URI naming its type.
+    pub fn absolute_uri(uri: &'a str, data: &'a [u8]) -> Self {
+        Payload::AbsoluteUri {
+            uri: borrowed(uri),
+            data: borrowed(data),
+        }
+    }
+
+    /// Unknown payload borrowing its data.
+    pub fn unknown(data: &'a [u8]) -> Self {
+        Payload::Unknown(borrowed(data))
+    }
+
     fn len(&self) -> usize {
         match self {
             Payload::RTD(rtd) => rtd.len(),
+            Payload::Media { data, .. }
+            | Payload::AbsoluteUri { data, .. }
+            | Payload::Unknown(data) => data.len(),
+            Payload::Empty => 0,
         }
     }
 
-    fn write(&self, buf: &mut Buffer) -> Result<'a, ()> {
+    fn write(&self, buf: &mut Buffer) -> Result<()> {
         match self {
             Payload::RTD(rtd) => rtd.write(buf),
+            Payload::Media { data, .. }
+            | Payload::AbsoluteUri { data, .. }
+            | Payload::Unknown(data) => write_all(buf, data),
+            Payload::Empty => Ok(()),
         }
     }
 
     fn type_len(&self) -> usize {
         match self {
             Payload::RTD(rtd) => rtd.type_len(),
+            Payload::Media { type_, .. } => type_.len(),
+            Payload::AbsoluteUri { uri, .. } => uri.len(),
+            Payload::Empty | Payload::Unknown(_) => 0,
         }
     }
 
-    fn write_type(&self, buf: &mut Buffer) -> Result<'a, ()> {
+    /// Wire type name, see [`Record::get_type`].
+    fn type_name(&self) -> Result<TypeName> {
+        let mut type_name = heapless::String::new();
+        match self {
+            Payload::RTD(rtd) => match rtd {
+                RecordType::Text { .. } => type_name.push_str("T"),
+                RecordType::Uri { .. } => type_name.push_str("U"),
+                RecordType::External { domain, type_, .. } => type_name
+                    .push_str(domain)
+                    .and_then(|()| type_name.push(':'))
+                    .and_then(|()| type_name.push_str(type_)),
+                RecordType::SmartPoster(_) => type_name.push_str("Sp"),
+                #[cfg(feature = "cbor")]
+                RecordType::Cbor(_) => type_name.push_str(CBOR_TYPE),
+            },
+            Payload::Media { type_, .. } => type_name.push_str(type_),
+            Payload::AbsoluteUri { uri, .. } => type_name.push_str(uri),
+            Payload::Empty | Payload::Unknown(_) => Ok(()),
+        }
+        .map_err(|_| Error::FieldTooLong)?;
+        Ok(type_name)
+    }
+
+    fn write_type(&self, buf: &mut Buffer) -> Result<()> {
         match self {
             Payload::RTD(rtd) => rtd.write_type(buf),
+            Payload::Media { type_, .. } => {
+                check_media_type(type_)?;
+                write_all(buf, type_.as_bytes())
+            }
+            Payload::AbsoluteUri { uri, .. } => write_all(buf, uri.as_bytes()),
+            Payload::Empty | Payload::Unknown(_) => Ok(()),
         }
     }
 
@@ -263,24 +712,69 @@ impl<'a> Payload<'a> {
     where
         T: dcbor::CBOREncodable,
     {
-        Payload::RTD(RecordType::Cbor(x.to_cbor_data()))
+        Payload::RTD(RecordType::Cbor(Cow::Owned(x.to_cbor_data())))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Payload<'_> {
+    /// Copies whatever the payload still borrows, so it outlives its input.
+    pub fn into_owned(self) -> Payload<'static> {
+        match self {
+            Payload::RTD(rtd) => Payload::RTD(rtd.into_owned()),
+            Payload::Media { type_, data } => Payload::Media {
+                type_: Cow::Owned(type_.into_owned()),
+                data: Cow::Owned(data.into_owned()),
+            },
+            Payload::AbsoluteUri { uri, data } => Payload::AbsoluteUri {
+                uri: Cow::Owned(uri.into_owned()),
+                data: Cow::Owned(data.into_owned()),
+            },
+            Payload::Empty => Payload::Empty,
+            Payload::Unknown(data) => Payload::Unknown(Cow::Owned(data.into_owned())),
+        }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Record<'a> {
-    id: Option<&'a [u8]>,
+    id: Option<id::IdField<'a>>,
     pub payload: Payload<'a>,
+    chunk_size: Option<NonZeroUsize>,
 }
 
 impl<'a> Record<'a> {
     pub fn new(id: Option<&'a [u8]>, payload: Payload<'a>) -> Self {
-        Self { id, payload }
+        Self {
+            id: id.map(Into::into),
+            payload,
+            chunk_size: None,
+        }
     }
 
     /// Identifier carried by the record, if any.
-    pub fn id(&self) -> Option<&'a [u8]> {
-        self.id
+    #[cfg_attr(not(feature = "alloc"), allow(clippy::needless_option_as_deref))]
+    pub fn id(&self) -> Option<&[u8]> {
+        self.id.as_deref()
+    }
+
+    /// Gives the record the ID `id`, or takes its ID away.
+    pub fn set_id(&mut self, id: Option<&'a [u8]>) {
+        self.id = id.map(Into::into);
+    }
+
+    /// Longest chunk the payload is written in, if it is split into chunks.
+    /// A parsed chunked record remembers the length of its first chunk.
+    pub fn chunk_size(&self) -> Option<NonZeroUsize> {
+        self.chunk_size
+    }
+
+    /// Splits the payload into chunks of at most `size` bytes when the record
+    /// is written, or writes it whole with `None`. A payload no longer than
+    /// `size` is written whole anyway. Chunked records are only read back by
+    /// this crate with `alloc`.
+    pub fn set_chunk_size(&mut self, size: Option<NonZeroUsize>) {
+        self.chunk_size = size;
     }
 
     /// Wire header of the record, given its position in the message. Every bit
@@ -304,51 +798,140 @@ impl<'a> Record<'a> {
         header
     }
 
-    #[cfg(feature = "cbor")]
-    pub fn is_type_cbor(&self) -> bool {
-        matches!(&self.payload, Payload::RTD(RecordType::Cbor(_)))
+    /// Writes the record as record `index` of the `count` records of a
+    /// message, in chunks if its payload is longer than its chunk size.
+    fn write(&self, buf: &mut Buffer, index: usize, count: usize) -> Result<()> {
+        if self.payload == Payload::Empty && self.id.is_some() {
+            return Err(Error::NonEmptyField(Field::Id));
+        }
+        let header = self.header(index, count);
+        let Some(size) = self.chunked_size() else {
+            return self.write_fields(buf, header, self.payload.len(), |buf| {
+                self.payload.write(buf)
+            });
+        };
+        let payload = self.payload()?;
+        let mut chunks = payload.chunks(size).peekable();
+        let mut initial = true;
+        while let Some(chunk) = chunks.next() {
+            let last = chunks.peek().is_none();
+            let chunk_header = header.chunk(initial, last, chunk.len());
+            self.write_fields(buf, chunk_header, chunk.len(), |buf| write_all(buf, chunk))?;
+            initial = false;
+        }
+        Ok(())
     }
 
-    #[cfg(feature = "alloc")]
-    pub fn get_type(&self) -> String {
-        use alloc::string::ToString;
+    /// Chunk size the payload is actually split by, being longer than it.
+    fn chunked_size(&self) -> Option<usize> {
+        self.chunk_size
+            .map(NonZeroUsize::get)
+            .filter(|&size| size < self.payload.len())
+    }
 
-        match &self.payload {
-            Payload::RTD(rtd) => match rtd {
-                RecordType::Text { .. } => "T".to_string(),
-                RecordType::External { domain, type_, .. } => format!("{domain}:{type_}"),
-                #[cfg(feature = "cbor")]
-                RecordType::Cbor(_) => CBOR_TYPE.to_string(),
-            },
+    /// Length of the record on the wire, chunks included.
+    fn encoded_len(&self) -> usize {
+        let length_len = |length| {
+            if length <= MAX_SHORT_PAYLOAD_LEN {
+                1
+            } else {
+                4
+            }
+        };
+        let payload_length = self.payload.len();
+        let id_len = self.id.as_ref().map_or(0, |id| 1 + id.len());
+        let mut len = id_len + self.payload.type_len() + payload_length;
+        match self.chunked_size() {
+            Some(size) => {
+                let (full, rest) = (payload_length / size, payload_length % size);
+                // Each chunk has its header, type length and payload length.
+                len += full * (2 + length_len(size));
+                if rest > 0 {
+                    len += 2 + length_len(rest);
+                }
+            }
+            _ => len += 2 + length_len(payload_length),
         }
+        len
     }
-    #[cfg(not(feature = "alloc"))]
-    pub fn get_type(&self) -> Result<'a, heapless::String<MAX_FIELD_LEN>> {
-        let mut type_name = heapless::String::new();
-        match &self.payload {
-            Payload::RTD(rtd) => match rtd {
-                RecordType::Text { .. } => type_name.push_str("T"),
-                RecordType::External { domain, type_, .. } => type_name
-                    .push_str(domain)
-                    .and_then(|()| type_name.push(':'))
-                    .and_then(|()| type_name.push_str(type_)),
-                #[cfg(feature = "cbor")]
-                RecordType::Cbor(_) => type_name.push_str(CBOR_TYPE),
-            },
+
+    /// Writes the fields announced by `header`, the payload being written by
+    /// `write_payload`. The type is only written when `header` names one.
+    fn write_fields(
+        &self,
+        buf: &mut Buffer,
+        header: Header,
+        payload_length: usize,
+        write_payload: impl FnOnce(&mut Buffer) -> Result<()>,
+    ) -> Result<()> {
+        let typed = header.type_name_format() != TypeNameFormat::Unchanged;
+        let id = if header.id_length() { self.id() } else { None };
+        // Header
+        write_u8(buf, header.0)?;
+        // Type Length
+        let type_length = if typed { self.payload.type_len() } else { 0 };
+        if type_length > MAX_FIELD_LEN {
+            return Err(Error::FieldTooLong);
         }
-        .map_err(|_| Error::BufferTooSmall)?;
-        Ok(type_name)
+        write_u8(buf, type_length as u8)?;
+        // Payload Length
+        if header.short_record() {
+            write_u8(buf, payload_length as u8)?;
+        } else {
+            let payload_length = u32::try_from(payload_length).map_err(|_| Error::FieldTooLong)?;
+            write_all(buf, &payload_length.to_be_bytes())?;
+        }
+        // ID Length
+        if let Some(id) = id {
+            if id.len() > MAX_FIELD_LEN {
+                return Err(Error::FieldTooLong);
+            }
+            write_u8(buf, id.len() as u8)?;
+        }
+        // Type
+        if typed {
+            self.payload.write_type(buf)?;
+        }
+        // ID
+        if let Some(id) = id {
+            write_all(buf, id)?;
+        }
+        // Payload
+        write_payload(buf)
+    }
+
+    #[cfg(feature = "cbor")]
+    pub fn is_type_cbor(&self) -> bool {
+        matches!(&self.payload, Payload::RTD(RecordType::Cbor(_)))
+    }
+
+    /// Wire type name of the record. It never exceeds what the type length
+    /// octet can announce, so a longer name is reported as
+    /// [`Error::FieldTooLong`].
+    pub fn get_type(&self) -> Result<TypeName> {
+        self.payload.type_name()
     }
 
     /// Encoded payload of the record.
-    pub fn payload(&self) -> Result<'a, Buffer> {
+    pub fn payload(&self) -> Result<Buffer> {
         let mut buf = Buffer::new();
         self.payload.write(&mut buf)?;
         Ok(buf)
     }
+
+    /// Copies whatever the record still borrows, so it outlives its input.
+    #[cfg(feature = "alloc")]
+    pub fn into_owned(self) -> Record<'static> {
+        Record {
+            id: self.id.map(|id| Cow::Owned(id.into_owned())),
+            payload: self.payload.into_owned(),
+            chunk_size: self.chunk_size,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Message<'a> {
     #[cfg(feature = "alloc")]
     pub records: Vec<Record<'a>>,
@@ -357,246 +940,119 @@ pub struct Message<'a> {
 }
 
 impl<'a> Message<'a> {
-    #[cfg(feature = "alloc")]
-    pub fn append_record(&mut self, record: Record<'a>) {
-        self.records.push(record);
+    /// Records of the message, in order.
+    pub fn records(&self) -> &[Record<'a>] {
+        &self.records
     }
 
-    #[cfg(not(feature = "alloc"))]
-    pub fn append_record(&mut self, record: Record<'a>) -> Result<'a, ()> {
+    /// Appends `record` at the end of the message. Without `alloc` the message
+    /// holds at most 8 records and reports [`Error::BufferTooSmall`] once it is
+    /// full; with `alloc` the append always succeeds.
+    pub fn append_record(&mut self, record: Record<'a>) -> Result<()> {
+        #[cfg(feature = "alloc")]
+        self.records.push(record);
         // `push` hands the record back untouched when the message is full, so
         // a rejected append leaves the message exactly as it was.
-        self.records.push(record).map_err(|_| Error::BufferTooSmall)
+        #[cfg(not(feature = "alloc"))]
+        self.records
+            .push(record)
+            .map_err(|_| Error::BufferTooSmall)?;
+        Ok(())
+    }
+
+    /// Inserts `record` at `index`, shifting the records after it. Like
+    /// [`Message::append_record`], it reports [`Error::BufferTooSmall`] when
+    /// the message is full and then leaves it untouched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of records.
+    pub fn insert_record(&mut self, index: usize, record: Record<'a>) -> Result<()> {
+        #[cfg(feature = "alloc")]
+        self.records.insert(index, record);
+        #[cfg(not(feature = "alloc"))]
+        self.records
+            .insert(index, record)
+            .map_err(|_| Error::BufferTooSmall)?;
+        Ok(())
+    }
+
+    /// Removes and returns the record at `index`, shifting the records after
+    /// it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_record(&mut self, index: usize) -> Record<'a> {
+        self.records.remove(index)
+    }
+
+    /// Removes and returns the first record carrying `id`.
+    pub fn remove_id(&mut self, id: &[u8]) -> Option<Record<'a>> {
+        let (index, _) = self.find_id(id)?;
+        Some(self.records.remove(index))
+    }
+
+    /// Puts `record` in place of the record at `index`, which is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn replace_record(&mut self, index: usize, record: Record<'a>) -> Record<'a> {
+        core::mem::replace(&mut self.records[index], record)
+    }
+
+    /// Keeps only the records for which `keep` returns `true`, in order.
+    pub fn retain_records(&mut self, keep: impl FnMut(&Record<'a>) -> bool) {
+        self.records.retain(keep)
+    }
+
+    /// Index and record of the first record whose wire type name, as returned
+    /// by [`Record::get_type`], is `type_name`: `"T"` for a Text record,
+    /// `"cbor.io:cbor"` or any other `domain:type` for an external record.
+    /// Names compare as their type name format requires, see
+    /// [`TypeNameFormat::type_eq`].
+    pub fn find_type(&self, type_name: &str) -> Option<(usize, &Record<'a>)> {
+        self.records.iter().enumerate().find(|(_, record)| {
+            record
+                .get_type()
+                .is_ok_and(|name| TypeNameFormat::from(&record.payload).type_eq(&name, type_name))
+        })
+    }
+
+    /// Records whose type name format is `tnf`, in order.
+    pub fn records_with_tnf(&self, tnf: TypeNameFormat) -> impl Iterator<Item = &Record<'a>> {
+        self.records
+            .iter()
+            .filter(move |record| TypeNameFormat::from(&record.payload) == tnf)
     }
 
-    pub fn to_vec(&self) -> Result<'a, Buffer> {
+    /// Serializes the message. The Message Begin and Message End flags follow
+    /// from the position of each record, whatever edits led to it.
+    pub fn to_vec(&self) -> Result<Buffer> {
         if self.records.is_empty() {
             return Err(Error::EmptyMessage);
         }
         let mut buf = Buffer::new();
         for (index, record) in self.records.iter().enumerate() {
-            let payload_length = record.payload.len();
-            let header = record.header(index, self.records.len());
-            let short_record = header.short_record();
-            // Header
-            write_u8(&mut buf, header.0)?;
-            // Type Length
-            let type_length = record.payload.type_len();
-            if type_length > MAX_FIELD_LEN {
-                return Err(Error::FieldTooLong);
-            }
-            write_u8(&mut buf, type_length as u8)?;
-            // Payload Length
-            if short_record {
-                write_u8(&mut buf, payload_length as u8)?;
-            } else {
-                let payload_length =
-                    u32::try_from(payload_length).map_err(|_| Error::FieldTooLong)?;
-                write_all(&mut buf, &payload_length.to_be_bytes())?;
-            }
-            // ID Length
-            if let Some(id) = &record.id {
-                if id.len() > MAX_FIELD_LEN {
-                    return Err(Error::FieldTooLong);
-                }
-                write_u8(&mut buf, id.len() as u8)?;
-            }
-            // Type
-            record.payload.write_type(&mut buf)?;
-            // ID
-            if let Some(id) = &record.id {
-                write_all(&mut buf, id)?;
-            }
-            // Payload
-            record.payload.write(&mut buf)?;
+            record.write(&mut buf, index, self.records.len())?;
         }
         Ok(buf)
     }
-}
-
-impl<'a> TryFrom<&'a [u8]> for Message<'a> {
-    type Error = Error<'a>;
 
-    fn try_from(slice: &'a [u8]) -> Result<'a, Self> {
-        if slice.is_empty() {
-            return Err(Error::SliceTooShort);
-        }
-        let mut records = Vec::new();
-        let mut offset = 0;
-        // Consumes the next `$len` bytes. `offset` is never advanced past
-        // `slice.len()`, so the remaining length is computed by subtraction
-        // instead of adding an encoded length to `offset`: a length field read
-        // from the input can be as large as `u32::MAX` and would otherwise
-        // overflow the addition on a 32-bit or smaller pointer width.
-        macro_rules! take {
-            ($len:expr) => {{
-                let len = $len;
-                if len > slice.len() - offset {
-                    return Err(Error::SliceTooShort);
-                }
-                let start = offset;
-                offset += len;
-                &slice[start..offset]
-            }};
-        }
-        let mut ended = false;
-        while offset < slice.len() {
-            // Nothing may follow the record that ended the message.
-            if ended {
-                return Err(Error::InvalidFraming);
-            }
-            // Header
-            let header = Header(take!(1)[0]);
-            // Only the first record begins the message.
-            if header.message_begin() != records.is_empty() {
-                return Err(Error::InvalidFraming);
-            }
-            // A chunk carries part of a payload, which is never a record on
-            // its own.
-            if header.message_chunk() {
-                return Err(Error::UnsupportedChunkedRecord);
-            }
-            ended = header.message_end();
-            // Type Length
-            let type_length = take!(1)[0] as usize;
-            // Payload Length
-            let payload_length = if header.short_record() {
-                take!(1)[0] as usize
-            } else {
-                let bytes = take!(4);
-                let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-                usize::try_from(length).map_err(|_| Error::SliceTooShort)?
-            };
-            // ID Length
-            let id_length = if header.id_length() {
-                take!(1)[0] as usize
-            } else {
-                0
-            };
-            // Type
-            let type_ = core::str::from_utf8(take!(type_length))?;
-            // ID
-            let id = if header.id_length() {
-                Some(take!(id_length))
-            } else {
-                None
-            };
-            // Payload
-            let payload_data = take!(payload_length);
-            let payload = match header.type_name_format() {
-                TypeNameFormat::NfcWellKnown => Payload::RTD(match type_ {
-                    "T" => {
-                        if payload_data.is_empty() {
-                            return Err(Error::SliceTooShort);
-                        }
-                        let status = payload_data[0];
-                        if status & TEXT_RESERVED_MASK != 0 {
-                            return Err(Error::InvalidTextStatus);
-                        }
-                        let enc_len = (status & TEXT_LANGUAGE_LEN_MASK) as usize;
-                        let is_utf16 = (status & TEXT_UTF16_MASK) != 0;
-                        if enc_len == 0 {
-                            return Err(Error::InvalidLanguageCode);
-                        }
-                        if payload_data.len() < enc_len + 1 {
-                            return Err(Error::SliceTooShort);
-                        }
-                        let enc = core::str::from_utf8(&payload_data[1..enc_len + 1])?;
-                        if !enc.is_ascii() {
-                            return Err(Error::InvalidLanguageCode);
-                        }
-                        let txt = if is_utf16 {
-                            #[cfg(not(feature = "alloc"))]
-                            return Err(Error::UnsupportedEncoding);
-                            #[cfg(feature = "alloc")]
-                            {
-                                let mut utf16_bytes = &payload_data[enc_len + 1..];
-                                // Ensure the byte slice has an even length (UTF-16 is 2 bytes per unit)
-                                if utf16_bytes.len() % 2 != 0 {
-                                    return Err(Error::UTF16OddLength(utf16_bytes.len()));
-                                }
-                                // A byte order mark chooses the endianness and
-                                // is not part of the text. Big endian is the
-                                // default when it is absent.
-                                let little_endian = match utf16_bytes.get(..2) {
-                                    Some([0xFF, 0xFE]) => {
-                                        utf16_bytes = &utf16_bytes[2..];
-                                        true
-                                    }
-                                    Some([0xFE, 0xFF]) => {
-                                        utf16_bytes = &utf16_bytes[2..];
-                                        false
-                                    }
-                                    _ => false,
-                                };
-                                // Convert the byte slice into u16 units
-                                let utf16_units: Vec<u16> = utf16_bytes
-                                    .chunks(2)
-                                    .map(|chunk| {
-                                        let unit = [chunk[0], chunk[1]];
-                                        if little_endian {
-                                            u16::from_le_bytes(unit)
-                                        } else {
-                                            u16::from_be_bytes(unit)
-                                        }
-                                    })
-                                    .collect();
-                                String::from_utf16(&utf16_units).map_err(|_| Error::UTF16Decode)?
-                            }
-                        } else {
-                            #[cfg(not(feature = "alloc"))]
-                            {
-                                core::str::from_utf8(&payload_data[enc_len + 1..])?
-                            }
-                            #[cfg(feature = "alloc")]
-                            String::from_utf8(payload_data[enc_len + 1..].to_vec())?
-                        };
-                        RecordType::Text { enc, txt }
-                    }
-                    t => return Err(Error::UnsupportedRecordType(t)),
-                }),
-                TypeNameFormat::NfcExternal => match type_ {
-                    #[cfg(all(feature = "cbor", not(feature = "alloc")))]
-                    CBOR_TYPE => Payload::RTD(RecordType::Cbor(payload_data)),
-                    #[cfg(all(feature = "cbor", feature = "alloc"))]
-                    CBOR_TYPE => Payload::RTD(RecordType::Cbor(payload_data.to_vec())),
-                    _ => {
-                        if let Some(index) = type_.find(':') {
-                            let domain = &type_[..index];
-                            let type_ = &type_[index + 1..];
-                            check_external_type(domain, type_)?;
-                            Payload::RTD(RecordType::External {
-                                domain,
-                                type_,
-                                data: payload_data,
-                            })
-                        } else {
-                            return Err(Error::InvalidExternalType(type_));
-                        }
-                    }
-                },
-                tnf => return Err(Error::UnsupportedTypeNameFormat(tnf)),
-            };
-            #[cfg(feature = "alloc")]
-            records.push(Record { id, payload });
-            #[cfg(not(feature = "alloc"))]
-            records
-                .push(Record { id, payload })
-                .map_err(|_| Error::SliceTooShort)?;
-        }
-        // The last record has to end the message.
-        if !ended {
-            return Err(Error::InvalidFraming);
+    /// Copies whatever the message still borrows from the slice it was parsed
+    /// from, so it can be stored or sent to another thread once the slice is
+    /// gone.
+    #[cfg(feature = "alloc")]
+    pub fn into_owned(self) -> Message<'static> {
+        Message {
+            records: self.records.into_iter().map(Record::into_owned).collect(),
         }
-        Ok(Message { records })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    #[cfg(feature = "alloc")]
-    use alloc::string::ToString;
-
     use super::*;
 
     #[test]
@@ -606,13 +1062,7 @@ mod tests {
             0x65, 0x78, 0x74, 0x20, 0xf0, 0x9f, 0xa6, 0x80,
         ];
         let mut msg = Message::default();
-        let txt = "UTF-8 text 🦀";
-        #[cfg(feature = "alloc")]
-        let txt = txt.to_string();
-        let rec1 = Record::new(None, Payload::RTD(RecordType::Text { enc: "fr", txt }));
-        #[cfg(feature = "alloc")]
-        msg.append_record(rec1);
-        #[cfg(not(feature = "alloc"))]
+        let rec1 = Record::new(None, Payload::RTD(RecordType::text("fr", "UTF-8 text 🦀")));
         msg.append_record(rec1).unwrap();
         assert_eq!(msg, Message::try_from(raw.as_slice()).unwrap());
         assert_eq!(&raw, msg.to_vec().unwrap().as_slice());
@@ -626,14 +1076,8 @@ mod tests {
             0x74, 0x00, 0x20, 0xd8, 0x3e, 0xdd, 0x80,
         ];
         let mut msg = Message::default();
-        let rec1 = Record::new(
-            None,
-            Payload::RTD(RecordType::Text {
-                enc: "fr",
-                txt: "UTF-16 text 🦀".to_string(),
-            }),
-        );
-        msg.append_record(rec1);
+        let rec1 = Record::new(None, Payload::RTD(RecordType::text("fr", "UTF-16 text 🦀")));
+        msg.append_record(rec1).unwrap();
         assert_eq!(msg, Message::try_from(raw.as_slice()).unwrap());
     }
     #[test]
@@ -644,15 +1088,8 @@ mod tests {
         let mut msg = Message::default();
         let rec1 = Record::new(
             None,
-            Payload::RTD(RecordType::External {
-                domain: "ex.com",
-                type_: "t",
-                data: &[0x61],
-            }),
+            Payload::RTD(RecordType::external("ex.com", "t", &[0x61])),
         );
-        #[cfg(feature = "alloc")]
-        msg.append_record(rec1);
-        #[cfg(not(feature = "alloc"))]
         msg.append_record(rec1).unwrap();
         assert_eq!(msg, Message::try_from(raw.as_slice()).unwrap());
         assert_eq!(&raw, msg.to_vec().unwrap().as_slice());
@@ -665,13 +1102,7 @@ mod tests {
             0x72, 0x61,
         ];
         let mut msg = Message::default();
-        #[cfg(feature = "alloc")]
-        let rec1 = Record::new(None, Payload::RTD(RecordType::Cbor(alloc::vec![0x61])));
-        #[cfg(not(feature = "alloc"))]
-        let rec1 = Record::new(None, Payload::RTD(RecordType::Cbor(&[0x61])));
-        #[cfg(feature = "alloc")]
-        msg.append_record(rec1);
-        #[cfg(not(feature = "alloc"))]
+        let rec1 = Record::new(None, Payload::RTD(RecordType::cbor(&[0x61])));
         msg.append_record(rec1).unwrap();
         assert_eq!(msg, Message::try_from(raw.as_slice()).unwrap());
         assert_eq!(&raw, msg.to_vec().unwrap().as_slice());
@@ -688,8 +1119,8 @@ mod tests {
             0x74, 0x00, 0x20, 0xd8, 0x3e, 0xdd, 0x80,
         ];
         assert_eq!(
-            Message::try_from(raw.as_slice()).unwrap_err(),
-            Error::UnsupportedEncoding
+            Message::try_from(raw.as_slice()).unwrap_err().error(),
+            &Error::UnsupportedEncoding
         );
     }
 
@@ -699,15 +1130,8 @@ mod tests {
     fn test_rtd_external_type_name() {
         let record = Record::new(
             None,
-            Payload::RTD(RecordType::External {
-                domain: "ex.com",
-                type_: "t",
-                data: &[0x61],
-            }),
+            Payload::RTD(RecordType::external("ex.com", "t", &[0x61])),
         );
-        #[cfg(feature = "alloc")]
-        assert_eq!(record.get_type(), "ex.com:t");
-        #[cfg(not(feature = "alloc"))]
         assert_eq!(record.get_type().unwrap().as_str(), "ex.com:t");
         assert_eq!(record.payload.type_len(), "ex.com:t".len());
     }
@@ -722,8 +1146,8 @@ mod tests {
             [0xD4, 0x04, 0x01, 0xC3, 0xA9, b':', b't', 0x61].as_slice(),
         ] {
             assert!(matches!(
-                Message::try_from(raw).unwrap_err(),
-                Error::InvalidExternalType(_)
+                Message::try_from(raw).unwrap_err().error(),
+                &Error::InvalidExternalType(_)
             ));
         }
         // the shortest name the grammar allows, spelled as given
@@ -751,15 +1175,8 @@ mod tests {
             let mut msg = Message::default();
             let rec1 = Record::new(
                 None,
-                Payload::RTD(RecordType::External {
-                    domain,
-                    type_,
-                    data: &[0x61],
-                }),
+                Payload::RTD(RecordType::external(domain, type_, &[0x61])),
             );
-            #[cfg(feature = "alloc")]
-            msg.append_record(rec1);
-            #[cfg(not(feature = "alloc"))]
             msg.append_record(rec1).unwrap();
             assert!(matches!(
                 msg.to_vec().unwrap_err(),
@@ -778,18 +1195,28 @@ mod tests {
         assert!(Message::try_from(record(0xD1).as_slice()).is_ok());
         // no record begins the message
         assert_eq!(
-            Message::try_from(record(0x51).as_slice()).unwrap_err(),
-            Error::InvalidFraming
+            Message::try_from(record(0x51).as_slice())
+                .unwrap_err()
+                .error(),
+            &Error::InvalidFraming
         );
         // no record ends the message
         assert_eq!(
-            Message::try_from(record(0x91).as_slice()).unwrap_err(),
-            Error::InvalidFraming
+            Message::try_from(record(0x91).as_slice())
+                .unwrap_err()
+                .error(),
+            &Error::InvalidFraming
         );
         // a chunk that also claims to end the message
+        #[cfg(not(feature = "alloc"))]
+        let expected = Error::UnsupportedChunkedRecord;
+        #[cfg(feature = "alloc")]
+        let expected = Error::InvalidChunk;
         assert_eq!(
-            Message::try_from(record(0xF1).as_slice()).unwrap_err(),
-            Error::UnsupportedChunkedRecord
+            Message::try_from(record(0xF1).as_slice())
+                .unwrap_err()
+                .error(),
+            &expected
         );
 
         let mut raw = [0u8; 16];
@@ -797,15 +1224,15 @@ mod tests {
         raw[..8].copy_from_slice(&record(0xD1));
         raw[8..].copy_from_slice(&record(0xD1));
         assert_eq!(
-            Message::try_from(raw.as_slice()).unwrap_err(),
-            Error::InvalidFraming
+            Message::try_from(raw.as_slice()).unwrap_err().error(),
+            &Error::InvalidFraming
         );
         // a second record beginning the message again
         raw[..8].copy_from_slice(&record(0x91));
         raw[8..].copy_from_slice(&record(0xD1));
         assert_eq!(
-            Message::try_from(raw.as_slice()).unwrap_err(),
-            Error::InvalidFraming
+            Message::try_from(raw.as_slice()).unwrap_err().error(),
+            &Error::InvalidFraming
         );
         // two records framing one message
         raw[..8].copy_from_slice(&record(0x91));
@@ -880,26 +1307,52 @@ mod tests {
         }
     }
 
+    /// UTF-8 text is already what the record holds, so it is not copied; only
+    /// UTF-16 text has to be decoded into an owned string.
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_rtd_text_borrows_utf8() {
+        let raw = [0xD1, 0x01, 0x04, b'T', 0x02, b'f', b'r', b'x'];
+        let msg = Message::try_from(raw.as_slice()).unwrap();
+        assert!(matches!(
+            &msg.records[0].payload,
+            Payload::RTD(RecordType::Text {
+                enc: Cow::Borrowed("fr"),
+                txt: Cow::Borrowed("x"),
+            })
+        ));
+
+        let raw = [0xD1, 0x01, 0x05, b'T', 0x82, b'f', b'r', 0x00, b'x'];
+        let msg = Message::try_from(raw.as_slice()).unwrap();
+        assert!(matches!(
+            &msg.records[0].payload,
+            Payload::RTD(RecordType::Text {
+                enc: Cow::Borrowed("fr"),
+                txt: Cow::Owned(_),
+            })
+        ));
+    }
+
     /// A status byte or language code outside the Text definition is refused.
     #[test]
     fn test_rtd_text_invalid_language() {
         // reserved bit set
         let raw = [0xD1, 0x01, 0x04, b'T', 0x42, b'f', b'r', b'x'];
         assert_eq!(
-            Message::try_from(raw.as_slice()).unwrap_err(),
-            Error::InvalidTextStatus
+            Message::try_from(raw.as_slice()).unwrap_err().error(),
+            &Error::InvalidTextStatus
         );
         // empty language code
         let raw = [0xD1, 0x01, 0x02, b'T', 0x00, b'x'];
         assert_eq!(
-            Message::try_from(raw.as_slice()).unwrap_err(),
-            Error::InvalidLanguageCode
+            Message::try_from(raw.as_slice()).unwrap_err().error(),
+            &Error::InvalidLanguageCode
         );
         // language code outside US-ASCII
         let raw = [0xD1, 0x01, 0x05, b'T', 0x02, 0xC3, 0xA9, b'x', b'y'];
         assert_eq!(
-            Message::try_from(raw.as_slice()).unwrap_err(),
-            Error::InvalidLanguageCode
+            Message::try_from(raw.as_slice()).unwrap_err().error(),
+            &Error::InvalidLanguageCode
         );
     }
 
@@ -907,14 +1360,8 @@ mod tests {
     #[test]
     fn test_rtd_text_language_is_validated_on_write() {
         for enc in ["", LONG_LANGUAGE, "é"] {
-            let txt = "x";
-            #[cfg(feature = "alloc")]
-            let txt = txt.to_string();
             let mut msg = Message::default();
-            let rec1 = Record::new(None, Payload::RTD(RecordType::Text { enc, txt }));
-            #[cfg(feature = "alloc")]
-            msg.append_record(rec1);
-            #[cfg(not(feature = "alloc"))]
+            let rec1 = Record::new(None, Payload::RTD(RecordType::text(enc, "x")));
             msg.append_record(rec1).unwrap();
             assert_eq!(msg.to_vec().unwrap_err(), Error::InvalidLanguageCode);
         }
@@ -934,20 +1381,10 @@ mod tests {
     #[test]
     fn test_payload_replaced_after_append() {
         let mut msg = Message::default();
-        let txt = "a";
-        #[cfg(feature = "alloc")]
-        let txt = txt.to_string();
-        let rec1 = Record::new(None, Payload::RTD(RecordType::Text { enc: "fr", txt }));
-        #[cfg(feature = "alloc")]
-        msg.append_record(rec1);
-        #[cfg(not(feature = "alloc"))]
+        let rec1 = Record::new(None, Payload::RTD(RecordType::text("fr", "a")));
         msg.append_record(rec1).unwrap();
 
-        msg.records[0].payload = Payload::RTD(RecordType::External {
-            domain: "ex.com",
-            type_: "t",
-            data: &[0x61],
-        });
+        msg.records[0].payload = Payload::RTD(RecordType::external("ex.com", "t", &[0x61]));
         let raw = msg.to_vec().unwrap();
         assert_eq!(
             Header(raw[0]).type_name_format(),
@@ -963,22 +1400,10 @@ mod tests {
         let mut msg = Message::default();
         let record = Record::new(
             None,
-            Payload::RTD(RecordType::External {
-                domain: "ex.com",
-                type_: "t",
-                data: &[0x61],
-            }),
+            Payload::RTD(RecordType::external("ex.com", "t", &[0x61])),
         );
-        #[cfg(feature = "alloc")]
-        {
-            msg.append_record(record.clone());
-            msg.append_record(record);
-        }
-        #[cfg(not(feature = "alloc"))]
-        {
-            msg.append_record(record.clone()).unwrap();
-            msg.append_record(record).unwrap();
-        }
+        msg.append_record(record.clone()).unwrap();
+        msg.append_record(record).unwrap();
         let raw = msg.to_vec().unwrap();
         // begin without end, then end without begin
         assert_eq!(raw[0] & 0xC0, 0x80);
@@ -993,11 +1418,7 @@ mod tests {
         let mut msg = Message::default();
         let record = Record::new(
             None,
-            Payload::RTD(RecordType::External {
-                domain: "ex.com",
-                type_: "t",
-                data: &[0x61],
-            }),
+            Payload::RTD(RecordType::external("ex.com", "t", &[0x61])),
         );
         for _ in 0..8 {
             msg.append_record(record.clone()).unwrap();
@@ -1023,13 +1444,9 @@ mod tests {
             let mut msg = Message::default();
             let rec1 = Record::new(
                 None,
-                Payload::RTD(RecordType::External {
-                    domain: "ex.com",
-                    type_: "t",
-                    data: &data,
-                }),
+                Payload::RTD(RecordType::external("ex.com", "t", &data)),
             );
-            msg.append_record(rec1);
+            msg.append_record(rec1).unwrap();
             let raw = msg.to_vec().unwrap();
             let short_record = raw[0] & 0x10 == 0x10;
             assert_eq!(short_record, length < 256);
@@ -1057,13 +1474,9 @@ mod tests {
             let mut msg = Message::default();
             let rec1 = Record::new(
                 Some(&id[..length]),
-                Payload::RTD(RecordType::External {
-                    domain: "ex.com",
-                    type_: "t",
-                    data: &[0x61],
-                }),
+                Payload::RTD(RecordType::external("ex.com", "t", &[0x61])),
             );
-            msg.append_record(rec1);
+            msg.append_record(rec1).unwrap();
             if expected {
                 let raw = msg.to_vec().unwrap();
                 assert_eq!(raw[3] as usize, length);
@@ -1079,19 +1492,20 @@ mod tests {
             let mut msg = Message::default();
             let rec1 = Record::new(
                 None,
-                Payload::RTD(RecordType::External {
-                    domain: &domain,
-                    type_: "t",
-                    data: &[0x61],
-                }),
+                Payload::RTD(RecordType::external(&domain, "t", &[0x61])),
             );
-            msg.append_record(rec1);
+            msg.append_record(rec1).unwrap();
             if expected {
                 let raw = msg.to_vec().unwrap();
                 assert_eq!(raw[1] as usize, length + 2);
+                assert_eq!(msg.records()[0].get_type().unwrap().len(), length + 2);
                 assert_eq!(msg, Message::try_from(raw.as_slice()).unwrap());
             } else {
                 assert_eq!(msg.to_vec().unwrap_err(), Error::FieldTooLong);
+                assert_eq!(
+                    msg.records()[0].get_type().unwrap_err(),
+                    Error::FieldTooLong
+                );
             }
         }
     }
@@ -1110,12 +1524,228 @@ mod tests {
                 0xC1, 0x01, length[0], length[1], length[2], length[3], b'T', 0x02, b'f', b'r',
             ];
             assert_eq!(
-                Message::try_from(raw.as_slice()).unwrap_err(),
-                Error::SliceTooShort
+                Message::try_from(raw.as_slice()).unwrap_err().error(),
+                &Error::SliceTooShort
             );
         }
     }
 
+    /// A parse error tells which byte of which field of which record it was
+    /// found at.
+    #[test]
+    fn test_parse_error_location() {
+        let parse = |second: &[u8]| {
+            let mut raw = Buffer::new();
+            write_all(&mut raw, &[0x91, 0x01, 0x04, b'T', 0x02, b'f', b'r', b'x']).unwrap();
+            write_all(&mut raw, second).unwrap();
+            let err = Message::try_from(raw.as_slice()).unwrap_err();
+            (err.offset(), err.record(), err.field())
+        };
+        // the second record begins the message again
+        assert_eq!(
+            parse(&[0xD1, 0x01, 0x01, b'T', 0x02]),
+            (8, 1, Field::Header)
+        );
+        // the second record stops in its payload length
+        assert_eq!(parse(&[0x51, 0x01]), (10, 1, Field::PayloadLength));
+        // the type of the second record is not UTF-8
+        assert_eq!(parse(&[0x51, 0x02, 0x00, b'T', 0xFF]), (12, 1, Field::Type));
+        // the text of the second record is not UTF-8 from its second byte
+        assert_eq!(
+            parse(&[0x51, 0x01, 0x05, b'T', 0x02, b'f', b'r', b'x', 0xFF]),
+            (16, 1, Field::Payload)
+        );
+        // an external type without a colon
+        assert_eq!(parse(&[0x54, 0x01, 0x00, b'T']), (11, 1, Field::Type));
+        // a type name format without a record type
+        assert_eq!(parse(&[0x57, 0x00, 0x00]), (8, 1, Field::Header));
+        // the last record does not end the message
+        assert_eq!(
+            parse(&[0x11, 0x01, 0x04, b'T', 0x02, b'f', b'r', b'x']),
+            (8, 1, Field::Header)
+        );
+
+        let raw = [0xD1, 0x01, 0x01, b'T', 0x40];
+        let err = Message::try_from(raw.as_slice()).unwrap_err();
+        assert_eq!(err.error(), &Error::InvalidTextStatus);
+        assert_eq!(
+            (err.offset(), err.record(), err.field()),
+            (4, 0, Field::Payload)
+        );
+    }
+
+    /// Errors do not borrow the input, so they can leave the function that owns
+    /// it, and explain themselves.
+    #[test]
+    #[rustversion::since(1.81)]
+    fn test_error_outlives_input() {
+        extern crate std;
+        use core::error::Error as _;
+        use std::string::ToString;
+
+        fn parse(raw: [u8; 5]) -> Result<usize> {
+            Ok(Message::try_from(raw.as_slice())?.records().len())
+        }
+        fn parse_located(raw: [u8; 5]) -> core::result::Result<usize, ParseError> {
+            Ok(Message::try_from(raw.as_slice())?.records().len())
+        }
+
+        let raw = [0xD4, 0x01, 0x00, b'T', 0x00];
+        let err = parse(raw).unwrap_err();
+        assert_eq!(err, Error::InvalidExternalType("T".into()));
+        assert_eq!(err.to_string(), "invalid external type name `T`");
+        let err = parse_located(raw).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid external type name `T` (record 0, type at byte 3)"
+        );
+        assert!(err.source().is_none());
+
+        let raw = [0xD1, 0x01, 0x01, 0xFF, 0x00];
+        let err = parse(raw).unwrap_err();
+        assert_eq!(err.to_string(), "text is not valid UTF-8");
+        assert!(err.source().is_some());
+        let err = parse_located(raw).unwrap_err();
+        assert!(err.source().unwrap().is::<core::str::Utf8Error>());
+    }
+
+    /// A quoted name is cut on a character boundary rather than dropped.
+    #[test]
+    fn test_error_name_truncated() {
+        let name = Name::from("0123456789012345678901234567890é");
+        assert_eq!(name.as_str().len(), 31);
+        assert!(name.is_truncated());
+        assert!(!Name::from("ex.com:t").is_truncated());
+    }
+
+    /// Edits keep the framing consistent and fail, rather than panic, once
+    /// the message is full.
+    #[test]
+    fn test_message_editing() {
+        let text = |txt| Record::new(None, Payload::RTD(RecordType::text("en", txt)));
+        let mut msg = Message::default();
+        msg.append_record(text("b")).unwrap();
+        msg.insert_record(0, text("a")).unwrap();
+        msg.append_record(Record::new(
+            Some(b"id"),
+            Payload::RTD(RecordType::external("ex.com", "t", b"c")),
+        ))
+        .unwrap();
+        msg.append_record(Record::new(None, Payload::media("text/plain", b"d")))
+            .unwrap();
+
+        assert_eq!(msg.find_type("T").map(|(index, _)| index), Some(0));
+        assert_eq!(msg.find_type("ex.com:t").map(|(index, _)| index), Some(2));
+        assert_eq!(msg.find_type("U"), None);
+        assert_eq!(
+            msg.records_with_tnf(TypeNameFormat::NfcWellKnown).count(),
+            2
+        );
+        assert!(msg
+            .records_with_tnf(TypeNameFormat::Media)
+            .eq([&msg.records[3]]));
+
+        let removed = msg.replace_record(0, text("z"));
+        assert_eq!(removed, text("a"));
+        let removed = msg.remove_id(b"id").unwrap();
+        assert_eq!(removed.id(), Some(b"id".as_slice()));
+        assert_eq!(msg.remove_id(b"id"), None);
+        msg.retain_records(|record| TypeNameFormat::from(&record.payload) != TypeNameFormat::Media);
+        assert_eq!(msg.remove_record(1), text("b"));
+        // The remaining record both begins and ends the message.
+        assert_eq!(msg.to_vec().unwrap()[0], 0xD1);
+
+        #[cfg(not(feature = "alloc"))]
+        {
+            while msg.records.len() < msg.records.capacity() {
+                msg.append_record(text("x")).unwrap();
+            }
+            assert_eq!(msg.insert_record(0, text("y")), Err(Error::BufferTooSmall));
+            assert_eq!(msg.records[0], text("z"));
+        }
+    }
+
+    /// A URI is abbreviated by its longest known prefix, and a reserved
+    /// prefix code is kept as read.
+    #[test]
+    fn test_uri_record() {
+        let rtd = RecordType::uri("urn:epc:id:sgtin:1");
+        assert_eq!(
+            rtd,
+            RecordType::Uri {
+                prefix: 0x1e,
+                uri: borrowed("sgtin:1"),
+            }
+        );
+        assert_eq!(RecordType::uri_prefix(0x1e), "urn:epc:id:");
+        assert_eq!(RecordType::uri("x").len(), 2);
+
+        let raw = [0xD1, 0x01, 0x03, b'U', 0x24, b'a', b'b'];
+        let msg = Message::try_from(raw.as_slice()).unwrap();
+        assert_eq!(
+            msg.records[0].payload,
+            Payload::RTD(RecordType::Uri {
+                prefix: 0x24,
+                uri: borrowed("ab"),
+            })
+        );
+        assert_eq!(RecordType::uri_prefix(0x24), "");
+        assert_eq!(msg.to_vec().unwrap().as_slice(), raw);
+        assert_eq!(
+            Message::try_from([0xD1, 0x01, 0x00, b'U'].as_slice())
+                .unwrap_err()
+                .error(),
+            &Error::SliceTooShort
+        );
+    }
+
+    /// Type names compare as their type name format requires.
+    #[test]
+    fn test_type_eq() {
+        assert!(TypeNameFormat::NfcExternal.type_eq("Ex.COM:t", "ex.com:T"));
+        assert!(!TypeNameFormat::NfcWellKnown.type_eq("T", "t"));
+        assert!(TypeNameFormat::Media.type_eq("Text/Plain;a=b", "text/plain;a=b"));
+        assert!(!TypeNameFormat::Media.type_eq("text/plain;a=b", "text/plain;a=B"));
+        assert!(!TypeNameFormat::AbsoluteUri.type_eq("HTTP://x", "http://x"));
+
+        // External types are dispatched case insensitively, well-known types
+        // are not.
+        let raw = [
+            0xD4, 0x0c, 0x01, b'C', b'B', b'O', b'R', b'.', b'i', b'o', b':', b'c', b'b', b'o',
+            b'r', 0x61,
+        ];
+        let msg = Message::try_from(raw.as_slice()).unwrap();
+        #[cfg(feature = "cbor")]
+        assert!(msg.records[0].is_type_cbor());
+        assert!(msg.find_type("cbor.io:CBOR").is_some());
+        assert_eq!(
+            Message::try_from([0xD1, 0x01, 0x01, b't', 0x00].as_slice())
+                .unwrap_err()
+                .error(),
+            &Error::UnsupportedRecordType("t".into())
+        );
+    }
+
+    /// Media records round-trip, and their type must be a media type.
+    #[test]
+    fn test_media_record() {
+        let raw = [
+            0xD2, 0x0A, 0x02, b't', b'e', b'x', b't', b'/', b'p', b'l', b'a', b'i', b'n', b'h',
+            b'i',
+        ];
+        let msg = Message::try_from(raw.as_slice()).unwrap();
+        assert_eq!(msg.records[0].payload, Payload::media("text/plain", b"hi"));
+        assert_eq!(msg.records[0].get_type().unwrap(), "text/plain");
+        assert_eq!(msg.to_vec().unwrap().as_slice(), raw);
+
+        let mut raw = raw;
+        raw[8] = b' ';
+        assert_eq!(
+            Message::try_from(raw.as_slice()).unwrap_err().error(),
+            &Error::InvalidMediaType("text/ lain".into())
+        );
+    }
+
     /// Every truncation of a valid message must be a recoverable error.
     #[test]
     fn test_parse_truncated() {
@@ -1129,6 +1759,24 @@ mod tests {
         assert!(Message::try_from(raw.as_slice()).is_ok());
     }
 
+    /// An owned message no longer depends on the buffer it was parsed from.
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_message_into_owned() {
+        fn assert_send<T: Send + 'static>(_: &T) {}
+
+        let raw = alloc::vec![
+            0x99, 0x01, 0x04, 0x02, b'T', 0x69, 0x64, 0x02, b'f', b'r', b'x', 0x54, 0x08, 0x01,
+            b'e', b'x', b'.', b'c', b'o', b'm', b':', b't', 0x61,
+        ];
+        let owned = Message::try_from(raw.as_slice()).unwrap().into_owned();
+        let expected = raw.clone();
+        drop(raw);
+        assert_send(&owned);
+        assert_eq!(owned.records[0].id(), Some(b"id".as_slice()));
+        assert_eq!(owned.to_vec().unwrap(), expected);
+    }
+
     /// Arbitrary input must never panic, whatever the header claims.
     #[test]
     fn test_parse_arbitrary_input() {
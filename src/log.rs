@@ -0,0 +1,253 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use defmt::{write, Format, Formatter};
+
+use crate::{
+    type2::{Tlv, Tlvs},
+    type3::Blocks,
+    Error, Message, Name, ParseError, Parsed, Payload, Record, RecordType,
+};
+#[cfg(feature = "serde")]
+use crate::{NdefData, NdefMessageInit, NdefRecordInit};
+
+impl Format for Name {
+    fn format(&self, f: Formatter<'_>) {
+        write!(f, "{=str}", self.as_str());
+        if self.is_truncated() {
+            write!(f, "...");
+        }
+    }
+}
+
+/// The same messages as [`core::fmt::Display`]. defmt keeps format strings
+/// out of the firmware, so they cost no flash.
+impl Format for Error {
+    fn format(&self, f: Formatter<'_>) {
+        match self {
+            Error::BufferTooSmall => write!(f, "buffer or message is full"),
+            Error::SliceTooShort => write!(f, "input ends before the field it announces"),
+            Error::UnsupportedEncoding => {
+                write!(f, "text encoding is not supported in this configuration")
+            }
+            Error::FieldTooLong => write!(f, "field is longer than its length can announce"),
+            Error::EmptyMessage => write!(f, "message holds no record"),
+            Error::InvalidLanguageCode => {
+                write!(f, "language code is empty, too long or not US-ASCII")
+            }
+            Error::InvalidTextStatus => write!(f, "text status byte has its reserved bit set"),
+            Error::InvalidFraming => {
+                write!(
+                    f,
+                    "message begin or end flag contradicts the record position"
+                )
+            }
+            Error::DuplicateId => write!(f, "ID is carried by another record"),
+            Error::UnresolvedId => write!(f, "no record carries the referenced ID"),
+            Error::InvalidTlv => write!(f, "TLV of the tag data area is invalid"),
+            Error::MissingNdefTlv => write!(f, "tag data area holds no NDEF message TLV"),
+            Error::InvalidCapabilityContainer => {
+                write!(f, "capability container does not allow reading NDEF")
+            }
+            Error::InvalidAddress => write!(f, "address is outside the tag memory"),
+            Error::ReadOnly => write!(f, "tag memory is read-only"),
+            Error::InvalidChecksum => write!(f, "checksum does not match the block"),
+            Error::WriteInProgress => write!(f, "tag is in the middle of a write"),
+            Error::UnexpectedStatus(sw) => {
+                write!(f, "tag answered with status word {=u16:04X}", sw)
+            }
+            Error::UnsupportedChunkedRecord => write!(f, "chunked records are not supported"),
+            Error::InvalidChunk => write!(f, "chunk does not continue a chunked payload"),
+            Error::NonEmptyField(field) => {
+                write!(f, "{} must be empty under this type name format", field)
+            }
+            Error::UnsupportedTypeNameFormat(tnf) => {
+                write!(f, "type name format {} is not supported", tnf)
+            }
+            Error::InvalidExternalType(name) => write!(f, "invalid external type name `{}`", name),
+            Error::InvalidMediaType(name) => write!(f, "invalid media type `{}`", name),
+            Error::UnsupportedRecordType(name) => {
+                write!(f, "record type `{}` is not supported", name)
+            }
+            Error::InvalidRecordData => write!(f, "data does not suit the record type"),
+            Error::TooManyRecords => write!(f, "message holds too many records"),
+            Error::PayloadTooLarge => write!(f, "payload is too large"),
+            Error::TotalPayloadTooLarge => write!(f, "payloads of the message are too large"),
+            Error::TooManyChunks => write!(f, "payload is split into too many chunks"),
+            Error::UTF8(error) => write!(
+                f,
+                "text is not valid UTF-8 after {=usize} bytes",
+                error.valid_up_to()
+            ),
+            #[cfg(feature = "alloc")]
+            Error::UTF16Decode => write!(f, "text is not valid UTF-16"),
+            #[cfg(feature = "alloc")]
+            Error::UTF16OddLength(len) => {
+                write!(f, "UTF-16 text has an odd length of {=usize} bytes", len)
+            }
+        }
+    }
+}
+
+impl Format for ParseError {
+    fn format(&self, f: Formatter<'_>) {
+        write!(
+            f,
+            "{} (record {=usize}, {} at byte {=usize})",
+            self.error(),
+            self.record(),
+            self.field(),
+            self.offset()
+        );
+    }
+}
+
+/// Text and URIs are written as strings, and other payloads as raw bytes that
+/// the host prints in hex.
+impl Format for RecordType<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        match self {
+            RecordType::Text { enc, txt } => write!(f, "Text [{=str}] {=str}", &**enc, &**txt),
+            RecordType::Uri { prefix, uri } => {
+                write!(
+                    f,
+                    "URI {=str}{=str}",
+                    RecordType::uri_prefix(*prefix),
+                    &**uri
+                )
+            }
+            RecordType::External {
+                domain,
+                type_,
+                data,
+            } => write!(
+                f,
+                "External {=str}:{=str} {=[u8]:x}",
+                &**domain, &**type_, &**data
+            ),
+            RecordType::SmartPoster(ndef) => write!(f, "Smart Poster {=[u8]:x}", &**ndef),
+            #[cfg(feature = "cbor")]
+            RecordType::Cbor(data) => write!(f, "CBOR {=[u8]:x}", &**data),
+        }
+    }
+}
+
+impl Format for Payload<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        match self {
+            Payload::RTD(rtd) => rtd.format(f),
+            Payload::Media { type_, data } => {
+                write!(f, "Media {=str} {=[u8]:x}", &**type_, &**data)
+            }
+            Payload::AbsoluteUri { uri, data } => {
+                write!(f, "Absolute URI {=str} {=[u8]:x}", &**uri, &**data)
+            }
+            Payload::Empty => write!(f, "Empty"),
+            Payload::Unknown(data) => write!(f, "Unknown {=[u8]:x}", &**data),
+        }
+    }
+}
+
+impl Format for Record<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        write!(f, "{}", self.payload);
+        if let Some(id) = self.id() {
+            write!(f, " id {=[u8]:x}", id);
+        }
+        if let Some(size) = self.chunk_size() {
+            write!(f, " in chunks of {=usize}", size.get());
+        }
+    }
+}
+
+impl Format for Message<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        write!(f, "{}", self.records());
+    }
+}
+
+impl Format for Parsed<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        write!(f, "{} {}", self.message, &self.diagnostics[..]);
+    }
+}
+
+impl Format for Tlv {
+    fn format(&self, f: Formatter<'_>) {
+        match self {
+            Tlv::LockControl(control) => write!(f, "LockControl {}", control),
+            Tlv::MemoryControl(control) => write!(f, "MemoryControl {}", control),
+            Tlv::Ndef(ndef) => write!(f, "Ndef {=[u8]:x}", &ndef[..]),
+            Tlv::Proprietary(value) => write!(f, "Proprietary {=[u8]:x}", &value[..]),
+            Tlv::Terminator => write!(f, "Terminator"),
+        }
+    }
+}
+
+/// The TLVs still to be read, errors included.
+impl Format for Tlvs<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        write!(f, "[");
+        for (index, tlv) in self.clone().enumerate() {
+            if index > 0 {
+                write!(f, ", ");
+            }
+            write!(f, "{}", tlv);
+        }
+        write!(f, "]");
+    }
+}
+
+/// The blocks still to be written, each with its number.
+impl Format for Blocks<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        write!(f, "[");
+        for (index, (number, block)) in self.clone().enumerate() {
+            if index > 0 {
+                write!(f, ", ");
+            }
+            write!(f, "{=usize} {=[u8]:x}", number, &block[..]);
+        }
+        write!(f, "]");
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Format for NdefMessageInit {
+    fn format(&self, f: Formatter<'_>) {
+        write!(f, "{}", &self.records[..]);
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Format for NdefRecordInit {
+    fn format(&self, f: Formatter<'_>) {
+        write!(f, "{=str}", self.record_type.as_str());
+        if let Some(media_type) = &self.media_type {
+            write!(f, " mediaType {=str}", media_type.as_str());
+        }
+        if let Some(id) = &self.id {
+            write!(f, " id {=str}", id.as_str());
+        }
+        if let Some(encoding) = &self.encoding {
+            write!(f, " encoding {=str}", encoding.as_str());
+        }
+        if let Some(lang) = &self.lang {
+            write!(f, " lang {=str}", lang.as_str());
+        }
+        if let Some(data) = &self.data {
+            write!(f, " {}", data);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Format for NdefData {
+    fn format(&self, f: Formatter<'_>) {
+        match self {
+            NdefData::Text(text) => write!(f, "{=str}", text.as_str()),
+            NdefData::Bytes(bytes) => write!(f, "{=[u8]:x}", &bytes[..]),
+            NdefData::Message(init) => init.format(f),
+        }
+    }
+}
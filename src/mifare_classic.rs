@@ -0,0 +1,469 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! NDEF on a MIFARE Classic card, whose sectors of 4 or 16 blocks each end
+//! with a trailer holding their keys and access bits. The MIFARE Application
+//! Directory in sector 0, and in sector 16 as well on cards of more than 16
+//! sectors, gives the application of each sector: the sectors of the NFC Forum
+//! application hold, in their data blocks and in order, TLVs encoded as in a
+//! [Type 2 Tag](crate::type2).
+//!
+//! The functions of this module take a dump of the card, its blocks back to
+//! back starting at block 0.
+//!
+//! ```
+//! use ndef::{mifare_classic, Message};
+//!
+//! let mut dump = [0; 1024];
+//! mifare_classic::format(&mut dump).unwrap();
+//!
+//! let msg = Message::builder().text("en", "hi").build().unwrap();
+//! mifare_classic::write_message(&mut dump, &msg).unwrap();
+//!
+//! let ndef = mifare_classic::read_ndef(&dump).unwrap();
+//! assert_eq!(Message::try_from(&ndef[..]).unwrap(), msg);
+//! ```
+
+use core::ops::Range;
+
+use crate::{
+    type2::{ndef_header, LONG_LENGTH, NDEF_MESSAGE, NULL, TERMINATOR},
+    write_u8, Buffer, Error, Message, Result,
+};
+
+/// Bytes of a block, the unit a MIFARE Classic card is read and written in.
+pub const BLOCK_LEN: usize = 16;
+
+/// Most sectors of a card, those of a MIFARE Classic 4K.
+pub const MAX_SECTORS: usize = 40;
+
+/// Application of a sector that holds NFC Forum data.
+pub const NFC_FORUM_AID: u16 = 0x03E1;
+
+/// Application of a sector that is not used.
+pub const FREE_AID: u16 = 0x0000;
+
+/// Public key A of the sectors of the MIFARE Application Directory.
+pub const MAD_KEY_A: [u8; 6] = [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5];
+
+/// Public key A of the sectors of the NFC Forum application.
+pub const NFC_KEY_A: [u8; 6] = [0xD3, 0xF7, 0xD3, 0xF7, 0xD3, 0xF7];
+
+/// Sector of the second part of the directory, on cards of more than 16
+/// sectors.
+const MAD2_SECTOR: usize = 16;
+/// Sectors the first part of the directory describes, itself included.
+const MAD1_SECTORS: usize = 16;
+/// Sectors made of 4 blocks; those after them are made of 16.
+const SMALL_SECTORS: usize = 32;
+
+const CRC_PRESET: u8 = 0xC7;
+const CRC_POLYNOMIAL: u8 = 0x1D;
+
+/// Access bits letting key A read the directory and key B write it.
+const MAD_ACCESS_BITS: [u8; 3] = [0x78, 0x77, 0x88];
+/// Access bits letting key A read and write the NFC Forum sectors.
+const NFC_ACCESS_BITS: [u8; 3] = [0x7F, 0x07, 0x88];
+
+/// Bits of the general purpose byte of sector 0 telling that the card holds
+/// a directory and several applications; the low bits give its version.
+const MAD_AVAILABLE: u8 = 0xC0;
+/// General purpose byte of an NFC Forum sector of mapping version 1.0 that can
+/// be read and written.
+const NFC_GPB: u8 = 0x40;
+/// Byte of a sector trailer holding the general purpose byte.
+const GPB: usize = 9;
+
+/// Blocks of `sector`.
+fn sector_blocks(sector: usize) -> Range<usize> {
+    if sector < SMALL_SECTORS {
+        4 * sector..4 * sector + 4
+    } else {
+        let start = 4 * SMALL_SECTORS + 16 * (sector - SMALL_SECTORS);
+        start..start + 16
+    }
+}
+
+/// Bytes of the block `block`.
+fn block_bytes(block: usize) -> Range<usize> {
+    BLOCK_LEN * block..BLOCK_LEN * (block + 1)
+}
+
+/// Bytes of the trailer of `sector`.
+fn trailer(sector: usize) -> Range<usize> {
+    block_bytes(sector_blocks(sector).end - 1)
+}
+
+/// Sectors wholly in `dump`.
+fn sectors_in(dump: &[u8]) -> usize {
+    (0..MAX_SECTORS)
+        .take_while(|sector| trailer(*sector).end <= dump.len())
+        .count()
+}
+
+/// Checksum of a part of the directory, over its bytes after the checksum.
+fn crc(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(CRC_PRESET, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ CRC_POLYNOMIAL
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// MIFARE Application Directory of a card, which gives the application of
+/// each of its sectors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Mad {
+    /// Sector of the card publisher, 6 bits, 0 when there is none.
+    pub publisher_sector: u8,
+    sectors: usize,
+    aids: [u16; MAX_SECTORS],
+}
+
+impl Mad {
+    /// Directory of a card of `sectors` sectors, all free. It is written in
+    /// sector 0 alone for a card of up to 16 sectors, and in sector 16 as well
+    /// for a larger one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sectors` is below 2 or above [`MAX_SECTORS`].
+    pub fn new(sectors: usize) -> Self {
+        assert!(
+            (2..=MAX_SECTORS).contains(&sectors),
+            "a MIFARE Classic card has 2 to 40 sectors"
+        );
+        Mad {
+            publisher_sector: 0,
+            sectors,
+            aids: [FREE_AID; MAX_SECTORS],
+        }
+    }
+
+    /// Reads the directory of the card `dump`, refused with
+    /// [`Error::InvalidCapabilityContainer`] when the general purpose byte of
+    /// sector 0 does not announce one, and with [`Error::InvalidChecksum`]
+    /// when a part of it does not match its CRC.
+    pub fn from_dump(dump: &[u8]) -> Result<Self> {
+        let sectors = sectors_in(dump);
+        if sectors < 2 {
+            return Err(Error::SliceTooShort);
+        }
+        let version = match dump[trailer(0)][GPB] {
+            gpb if gpb & MAD_AVAILABLE != MAD_AVAILABLE => 0,
+            gpb => gpb & 0x03,
+        };
+        let sectors = match version {
+            1 => sectors.min(MAD1_SECTORS),
+            2 if sectors > MAD2_SECTOR => sectors,
+            2 => return Err(Error::SliceTooShort),
+            _ => return Err(Error::InvalidCapabilityContainer),
+        };
+        let mut mad = Mad::new(sectors);
+        let mad1 = &dump[block_bytes(1).start..block_bytes(2).end];
+        mad.read_part(mad1, 1)?;
+        mad.publisher_sector = mad1[1] & 0x3F;
+        if sectors > MAD2_SECTOR {
+            let blocks = sector_blocks(MAD2_SECTOR);
+            let mad2 = &dump[block_bytes(blocks.start).start..block_bytes(blocks.end - 2).end];
+            mad.read_part(mad2, MAD2_SECTOR + 1)?;
+        }
+        Ok(mad)
+    }
+
+    /// Writes the directory in the card `dump`, along with the trailers of its
+    /// sectors, which get the public key A and the general purpose byte
+    /// announcing it. Key B is left as it is.
+    pub fn write(&self, dump: &mut [u8]) -> Result<()> {
+        if sectors_in(dump) < self.sectors {
+            return Err(Error::SliceTooShort);
+        }
+        let version = if self.sectors > MAD2_SECTOR { 2 } else { 1 };
+        let mad1 = block_bytes(1).start..block_bytes(2).end;
+        self.write_part(&mut dump[mad1], 1);
+        if version == 2 {
+            let blocks = sector_blocks(MAD2_SECTOR);
+            let mad2 = block_bytes(blocks.start).start..block_bytes(blocks.end - 2).end;
+            self.write_part(&mut dump[mad2], MAD2_SECTOR + 1);
+        }
+        for sector in [0, MAD2_SECTOR].into_iter().take(version as usize) {
+            let trailer = &mut dump[trailer(sector)];
+            trailer[..6].copy_from_slice(&MAD_KEY_A);
+            trailer[6..9].copy_from_slice(&MAD_ACCESS_BITS);
+            trailer[GPB] = MAD_AVAILABLE | version;
+        }
+        Ok(())
+    }
+
+    /// Sectors of the card.
+    pub fn sectors(&self) -> usize {
+        self.sectors
+    }
+
+    /// Application of `sector`, `None` for a sector of the directory itself or
+    /// past the end of the card.
+    pub fn aid(&self, sector: usize) -> Option<u16> {
+        (sector < self.sectors && !is_mad_sector(sector)).then(|| self.aids[sector])
+    }
+
+    /// Sets the application of `sector`, reporting [`Error::InvalidAddress`]
+    /// for a sector of the directory itself or past the end of the card.
+    pub fn set_aid(&mut self, sector: usize, aid: u16) -> Result<()> {
+        self.aid(sector).ok_or(Error::InvalidAddress)?;
+        self.aids[sector] = aid;
+        Ok(())
+    }
+
+    /// Sectors of the NFC Forum application, in order.
+    pub fn nfc_sectors(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.sectors).filter(|sector| self.aid(*sector) == Some(NFC_FORUM_AID))
+    }
+
+    /// Reads the applications of a part of the directory, `bytes`, starting
+    /// with that of `first`.
+    fn read_part(&mut self, bytes: &[u8], first: usize) -> Result<()> {
+        if crc(&bytes[1..]) != bytes[0] {
+            return Err(Error::InvalidChecksum);
+        }
+        for (sector, aid) in (first..self.sectors).zip(bytes[2..].chunks(2)) {
+            self.aids[sector] = u16::from_be_bytes([aid[0], aid[1]]);
+        }
+        Ok(())
+    }
+
+    fn write_part(&self, bytes: &mut [u8], first: usize) {
+        bytes.fill(0);
+        bytes[1] = self.publisher_sector & 0x3F;
+        for (sector, aid) in (first..self.sectors).zip(bytes[2..].chunks_mut(2)) {
+            aid.copy_from_slice(&self.aids[sector].to_be_bytes());
+        }
+        bytes[0] = crc(&bytes[1..]);
+    }
+}
+
+fn is_mad_sector(sector: usize) -> bool {
+    sector == 0 || sector == MAD2_SECTOR
+}
+
+/// Bytes of the data blocks of the NFC Forum sectors of `mad`, in order.
+fn nfc_data(mad: &Mad) -> impl Iterator<Item = usize> + '_ {
+    mad.nfc_sectors()
+        .flat_map(|sector| {
+            let blocks = sector_blocks(sector);
+            blocks.start..blocks.end - 1
+        })
+        .flat_map(block_bytes)
+}
+
+/// Directory of the card `dump`, after checking that the general purpose byte
+/// of each of its NFC Forum sectors has a major version of 1 and grants read
+/// access, and write access as well when `write` is set.
+fn nfc_sectors(dump: &[u8], write: bool) -> Result<Mad> {
+    let mad = Mad::from_dump(dump)?;
+    for sector in mad.nfc_sectors() {
+        let gpb = dump[trailer(sector)][GPB];
+        if gpb >> 6 != NFC_GPB >> 6 || (gpb >> 2) & 0x03 != 0 {
+            return Err(Error::InvalidCapabilityContainer);
+        }
+        if write && gpb & 0x03 != 0 {
+            return Err(Error::ReadOnly);
+        }
+    }
+    Ok(mad)
+}
+
+fn read(bytes: &mut impl Iterator<Item = u8>) -> Result<u8> {
+    bytes.next().ok_or(Error::SliceTooShort)
+}
+
+fn read_length(bytes: &mut impl Iterator<Item = u8>) -> Result<usize> {
+    match read(bytes)? {
+        LONG_LENGTH => {
+            let len = u16::from_be_bytes([read(bytes)?, read(bytes)?]);
+            // a length that fits in one byte is written in one byte
+            if len < LONG_LENGTH as u16 || len == u16::MAX {
+                return Err(Error::InvalidTlv);
+            }
+            Ok(len as usize)
+        }
+        len => Ok(len as usize),
+    }
+}
+
+/// Serialized NDEF message of the first NDEF Message TLV of the NFC Forum
+/// sectors of the card `dump`, to be parsed with [`Message::try_from`]. The
+/// sector trailers and the sectors of other applications are skipped.
+pub fn read_ndef(dump: &[u8]) -> Result<Buffer> {
+    let mad = nfc_sectors(dump, false)?;
+    let mut bytes = nfc_data(&mad).map(|pos| dump[pos]);
+    while let Some(tlv) = bytes.next() {
+        match tlv {
+            NULL => continue,
+            TERMINATOR => break,
+            NDEF_MESSAGE => {
+                let mut ndef = Buffer::new();
+                for _ in 0..read_length(&mut bytes)? {
+                    write_u8(&mut ndef, read(&mut bytes)?)?;
+                }
+                return Ok(ndef);
+            }
+            _ => {
+                for _ in 0..read_length(&mut bytes)? {
+                    read(&mut bytes)?;
+                }
+            }
+        }
+    }
+    Err(Error::MissingNdefTlv)
+}
+
+/// Writes `ndef`, a serialized NDEF message, in an NDEF Message TLV spread over
+/// the data blocks of the NFC Forum sectors of the card `dump`, followed by a
+/// Terminator TLV if there is room for it. A sector whose general purpose
+/// byte denies writing is reported as [`Error::ReadOnly`], and the card is
+/// left untouched when the message does not fit.
+pub fn write_ndef(dump: &mut [u8], ndef: &[u8]) -> Result<()> {
+    let mad = nfc_sectors(dump, true)?;
+    let tlv = ndef_header(ndef.len())?;
+    let free = nfc_data(&mad).count();
+    if tlv.len() + ndef.len() > free {
+        return Err(Error::BufferTooSmall);
+    }
+    let terminator = (tlv.len() + ndef.len() < free).then_some(&TERMINATOR);
+    let bytes = tlv.iter().chain(ndef).chain(terminator);
+    for (pos, byte) in nfc_data(&mad).zip(bytes) {
+        dump[pos] = *byte;
+    }
+    Ok(())
+}
+
+/// Writes `message` in the card `dump`, see [`write_ndef`].
+pub fn write_message(dump: &mut [u8], message: &Message<'_>) -> Result<()> {
+    write_ndef(dump, &message.to_vec()?)
+}
+
+/// Formats the card `dump` for NDEF: every sector but those of the directory
+/// is given to the NFC Forum application, with its public key A, and holds an
+/// empty NDEF message. Key B of every sector is left as it is.
+pub fn format(dump: &mut [u8]) -> Result<()> {
+    let sectors = sectors_in(dump);
+    if sectors < 2 {
+        return Err(Error::SliceTooShort);
+    }
+    let mut mad = Mad::new(sectors);
+    mad.publisher_sector = 1;
+    for sector in 1..sectors {
+        if is_mad_sector(sector) {
+            continue;
+        }
+        mad.set_aid(sector, NFC_FORUM_AID)?;
+        let blocks = sector_blocks(sector);
+        dump[block_bytes(blocks.start).start..block_bytes(blocks.end - 2).end].fill(0);
+        let trailer = &mut dump[trailer(sector)];
+        trailer[..6].copy_from_slice(&NFC_KEY_A);
+        trailer[6..9].copy_from_slice(&NFC_ACCESS_BITS);
+        trailer[GPB] = NFC_GPB;
+    }
+    mad.write(dump)?;
+    write_ndef(dump, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mad() {
+        let mut dump = [0; 1024];
+        format(&mut dump).unwrap();
+        assert_eq!(&dump[16..20], &[0x14, 0x01, 0x03, 0xE1]);
+        assert_eq!(
+            &dump[48..58],
+            &[0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5, 0x78, 0x77, 0x88, 0xC1]
+        );
+        assert_eq!(
+            &dump[112..122],
+            &[0xD3, 0xF7, 0xD3, 0xF7, 0xD3, 0xF7, 0x7F, 0x07, 0x88, 0x40]
+        );
+
+        let mad = Mad::from_dump(&dump).unwrap();
+        assert_eq!(mad.sectors(), 16);
+        assert_eq!(mad.publisher_sector, 1);
+        assert_eq!(mad.aid(0), None);
+        assert_eq!(mad.aid(15), Some(NFC_FORUM_AID));
+        assert_eq!(mad.aid(16), None);
+        assert!(mad.nfc_sectors().eq(1..16));
+
+        let mut corrupt = dump;
+        corrupt[20] = 0x00;
+        assert_eq!(Mad::from_dump(&corrupt), Err(Error::InvalidChecksum));
+        corrupt[57] = 0x00;
+        assert_eq!(
+            Mad::from_dump(&corrupt),
+            Err(Error::InvalidCapabilityContainer)
+        );
+        assert_eq!(Mad::from_dump(&dump[..128]).unwrap().sectors(), 2);
+    }
+
+    /// A 4K card has a second part of the directory in sector 16, and sectors
+    /// of 16 blocks after sector 31.
+    #[test]
+    fn test_mad2() {
+        let mut dump = [0; 4096];
+        format(&mut dump).unwrap();
+        assert_eq!(dump[trailer(0)][GPB], 0xC2);
+        assert_eq!(&dump[1024..1028], &[0xE8, 0x01, 0x03, 0xE1]);
+        assert_eq!(trailer(39), 4080..4096);
+
+        let mut mad = Mad::from_dump(&dump).unwrap();
+        assert_eq!(mad.sectors(), 40);
+        assert_eq!(mad.nfc_sectors().count(), 38);
+        assert_eq!(nfc_data(&mad).count(), 30 * 48 + 8 * 240);
+
+        mad.set_aid(39, FREE_AID).unwrap();
+        assert_eq!(mad.set_aid(16, FREE_AID), Err(Error::InvalidAddress));
+        assert_eq!(mad.set_aid(40, FREE_AID), Err(Error::InvalidAddress));
+        mad.write(&mut dump).unwrap();
+        assert_eq!(Mad::from_dump(&dump), Ok(mad));
+    }
+
+    /// The message skips the sector trailers and a sector of another
+    /// application.
+    #[test]
+    fn test_read_write() {
+        let msg = Message::builder()
+            .uri("https://foundation.xyz/passport")
+            .text("en", "Passport, the Bitcoin hardware wallet")
+            .build()
+            .unwrap();
+        let ndef = msg.to_vec().unwrap();
+        let mut dump = [0; 1024];
+        format(&mut dump).unwrap();
+        let mut mad = Mad::from_dump(&dump).unwrap();
+        mad.set_aid(2, 0x4801).unwrap();
+        mad.write(&mut dump).unwrap();
+        dump[128..176].fill(0xAA);
+
+        let before = dump;
+        write_message(&mut dump, &msg).unwrap();
+        assert_eq!(&dump[64..66], &[NDEF_MESSAGE, ndef.len() as u8]);
+        assert_eq!(&dump[66..112], &ndef[..46]);
+        assert_eq!(dump[trailer(1)], before[trailer(1)]);
+        assert_eq!(dump[128..176], [0xAA; 48]);
+        assert_eq!(&dump[192..192 + ndef.len() - 46], &ndef[46..]);
+        assert_eq!(read_ndef(&dump).unwrap(), ndef);
+
+        let long = [0; 14 * 48 - 1];
+        assert_eq!(write_ndef(&mut dump, &long), Err(Error::BufferTooSmall));
+        dump[trailer(3)][GPB] = 0x43;
+        assert_eq!(write_ndef(&mut dump, &[]), Err(Error::ReadOnly));
+        assert_eq!(read_ndef(&dump).unwrap(), ndef);
+        dump[trailer(3)][GPB] = 0x80;
+        assert_eq!(read_ndef(&dump), Err(Error::InvalidCapabilityContainer));
+    }
+}
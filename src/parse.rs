@@ -0,0 +1,990 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, string::String, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::num::NonZeroUsize;
+#[cfg(not(feature = "alloc"))]
+use heapless::Vec;
+
+#[cfg(feature = "cbor")]
+use crate::CBOR_TYPE;
+use crate::{
+    borrowed, check_external_type, check_media_type, Error, Field, Header, Message, ParseError,
+    Payload, Record, RecordType, TypeNameFormat, TEXT_LANGUAGE_LEN_MASK, TEXT_RESERVED_MASK,
+    TEXT_UTF16_MASK,
+};
+
+/// Diagnostics reported by [`Message::parse`]. Without `alloc` only the first
+/// 8 are kept.
+#[cfg(feature = "alloc")]
+pub type Diagnostics = Vec<Diagnostic>;
+#[cfg(not(feature = "alloc"))]
+pub type Diagnostics = Vec<Diagnostic, 8>;
+
+/// Where each record of a parsed message was read, see [`Parsed::layout`].
+/// Without `alloc` it holds as many entries as a message holds records.
+#[cfg(feature = "alloc")]
+pub type Layouts = Vec<RecordLayout>;
+#[cfg(not(feature = "alloc"))]
+pub type Layouts = Vec<RecordLayout, 8>;
+
+/// How [`Message::parse`] treats input that breaks the format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// Refuse the message at the first defect, like [`Message::try_from`].
+    #[default]
+    Strict,
+    /// Read past the defects named by [`Check`] and report each of them.
+    Lenient,
+    /// Read past the defects named by [`Check`], and leave out the records
+    /// that cannot be decoded instead of refusing the message.
+    Recovery,
+}
+
+/// A defect the parser can read past outside of [`Mode::Strict`]. Each one is
+/// an error of the strict parser, reported with the same [`Error`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Check {
+    /// The first record does not set message begin, or a later one does
+    MessageBegin,
+    /// The last record does not set message end
+    MessageEnd,
+    /// Bytes follow the record that set message end. They are ignored.
+    TrailingData,
+    /// The status byte of a Text record has its reserved bit set. The bit is
+    /// ignored.
+    TextStatus,
+}
+
+impl Check {
+    const fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Bounds on what a parsed message may hold, so that input from an untrusted
+/// tag cannot make the parser spend more than the caller allows. Each bound
+/// is refused with its own [`Error`]; none is set by default.
+///
+/// Payloads are not parsed as nested messages, so there is no depth to
+/// limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Limits {
+    /// Records in the message, chunks included. See [`Error::TooManyRecords`].
+    pub records: usize,
+    /// Bytes of a single payload, once reassembled from its chunks. See
+    /// [`Error::PayloadTooLarge`].
+    pub payload: usize,
+    /// Bytes of all the payloads of the message. See
+    /// [`Error::TotalPayloadTooLarge`].
+    pub total_payload: usize,
+    /// Chunks a single payload is split into. See [`Error::TooManyChunks`].
+    pub chunks: usize,
+}
+
+impl Limits {
+    /// No bound at all, which is what [`Message::try_from`] applies.
+    pub const NONE: Limits = Limits {
+        records: usize::MAX,
+        payload: usize::MAX,
+        total_payload: usize::MAX,
+        chunks: usize::MAX,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::NONE
+    }
+}
+
+/// Parser configuration for [`Message::parse`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ParseOptions {
+    mode: Mode,
+    enforced: u8,
+    limits: Limits,
+}
+
+impl ParseOptions {
+    /// Options parsing in `mode`, enforcing no check and with no limit.
+    pub const fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            enforced: 0,
+            limits: Limits::NONE,
+        }
+    }
+
+    /// Replaces the limits the input is held to.
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Limits the input is held to.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Keeps `check` an error whatever the mode.
+    pub const fn enforce(mut self, check: Check) -> Self {
+        self.enforced |= check.mask();
+        self
+    }
+
+    /// Mode the input is parsed in.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn is_enforced(&self, check: Check) -> bool {
+        self.mode == Mode::Strict || self.enforced & check.mask() != 0
+    }
+}
+
+/// Something [`Message::parse`] read past instead of refusing the message.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Diagnostic {
+    /// A defect named by [`Check`]; the record it was found in was kept.
+    Accepted(ParseError),
+    /// A record that could not be decoded, or bytes that could not be framed
+    /// as a record, left out of the message in [`Mode::Recovery`].
+    Skipped(ParseError),
+}
+
+impl Diagnostic {
+    /// Defect behind the diagnostic, with where it was found.
+    pub fn error(&self) -> &ParseError {
+        match self {
+            Diagnostic::Accepted(error) | Diagnostic::Skipped(error) => error,
+        }
+    }
+}
+
+/// Bytes a record of a parsed message was read from, which a lenient parse
+/// keeps even where they break the format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RecordLayout {
+    /// Offset of the record in the input, that of its first chunk if it is
+    /// chunked.
+    pub offset: usize,
+    /// Header byte as read, that of the first chunk if the record is chunked.
+    pub header: u8,
+    /// Chunks the payload was read in, 1 for a record that is not chunked.
+    pub chunks: usize,
+}
+
+/// Outcome of [`Message::parse`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Parsed<'a> {
+    pub message: Message<'a>,
+    /// Every defect read past, in input order. Always empty in
+    /// [`Mode::Strict`].
+    pub diagnostics: Diagnostics,
+    /// Where each record of the message was read, in the order of the
+    /// records.
+    pub layout: Layouts,
+}
+
+/// Cursor over a message being parsed. It knows which field of which record it
+/// is reading, so that an error can tell where it was found.
+struct Reader<'a> {
+    slice: &'a [u8],
+    offset: usize,
+    record: usize,
+    field: Field,
+    field_offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(slice: &'a [u8]) -> Self {
+        Self {
+            slice,
+            offset: 0,
+            record: 0,
+            field: Field::Header,
+            field_offset: 0,
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.slice.len() - self.offset
+    }
+
+    /// Starts reading `field` of the current record.
+    fn enter(&mut self, field: Field) {
+        self.field = field;
+        self.field_offset = self.offset;
+    }
+
+    /// Consumes the next `len` bytes. `offset` is never advanced past
+    /// `slice.len()`, so the remaining length is computed by subtraction
+    /// instead of adding an encoded length to `offset`: a length field read
+    /// from the input can be as large as `u32::MAX` and would otherwise
+    /// overflow the addition on a 32-bit or smaller pointer width.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ParseError> {
+        if len > self.remaining() {
+            return Err(self.error(Error::SliceTooShort));
+        }
+        let start = self.offset;
+        self.offset += len;
+        Ok(&self.slice[start..self.offset])
+    }
+
+    fn take_u8(&mut self) -> Result<u8, ParseError> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// `error`, found at the start of the field being read.
+    fn error(&self, error: Error) -> ParseError {
+        self.error_at(error, 0)
+    }
+
+    /// `error`, found `offset` bytes into the field being read.
+    fn error_at(&self, error: Error, offset: usize) -> ParseError {
+        ParseError::new(error, self.field_offset + offset, self.record, self.field)
+    }
+}
+
+/// A record as framed on the wire, before its type and payload are decoded.
+struct RawRecord<'a> {
+    header: Header,
+    header_offset: usize,
+    type_offset: usize,
+    type_: &'a [u8],
+    id: Option<&'a [u8]>,
+    payload_offset: usize,
+    payload: &'a [u8],
+}
+
+impl<'a> RawRecord<'a> {
+    /// Reads the header and the fields it announces, counting the payload
+    /// against `limits` before it is read.
+    fn read(
+        reader: &mut Reader<'a>,
+        limits: &Limits,
+        total_payload: &mut usize,
+    ) -> Result<Self, ParseError> {
+        // Header
+        reader.enter(Field::Header);
+        let header_offset = reader.offset;
+        let header = Header(reader.take_u8()?);
+        // Type Length
+        reader.enter(Field::TypeLength);
+        let type_length = reader.take_u8()? as usize;
+        // Payload Length
+        reader.enter(Field::PayloadLength);
+        let payload_length = if header.short_record() {
+            reader.take_u8()? as usize
+        } else {
+            let bytes = reader.take(4)?;
+            let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            usize::try_from(length).map_err(|_| reader.error(Error::SliceTooShort))?
+        };
+        if payload_length > limits.payload {
+            return Err(reader.error(Error::PayloadTooLarge));
+        }
+        *total_payload = total_payload.saturating_add(payload_length);
+        if *total_payload > limits.total_payload {
+            return Err(reader.error(Error::TotalPayloadTooLarge));
+        }
+        // ID Length
+        let id_length = if header.id_length() {
+            reader.enter(Field::IdLength);
+            reader.take_u8()? as usize
+        } else {
+            0
+        };
+        // Type
+        reader.enter(Field::Type);
+        let type_offset = reader.offset;
+        let type_ = reader.take(type_length)?;
+        // ID
+        let id = if header.id_length() {
+            reader.enter(Field::Id);
+            Some(reader.take(id_length)?)
+        } else {
+            None
+        };
+        // Payload
+        reader.enter(Field::Payload);
+        let payload_offset = reader.offset;
+        let payload = reader.take(payload_length)?;
+        Ok(Self {
+            header,
+            header_offset,
+            type_offset,
+            type_,
+            id,
+            payload_offset,
+            payload,
+        })
+    }
+
+    /// Decodes the type and payload of the record `index`.
+    fn decode(&self, index: usize, parser: &mut Parser) -> Result<Record<'a>, ParseError> {
+        let at = |error, offset, field| ParseError::new(error, offset, index, field);
+        // A chunk carries part of a payload, which can only be reassembled
+        // with an allocator.
+        #[cfg(not(feature = "alloc"))]
+        if self.header.message_chunk() {
+            return Err(at(
+                Error::UnsupportedChunkedRecord,
+                self.header_offset,
+                Field::Header,
+            ));
+        }
+        let type_ = core::str::from_utf8(self.type_)
+            .map_err(|e| at(e.into(), self.type_offset + e.valid_up_to(), Field::Type))?;
+        let type_error = |error| at(error, self.type_offset, Field::Type);
+        let mut warning = None;
+        let payload = match self.header.type_name_format() {
+            TypeNameFormat::NfcWellKnown => Payload::RTD(match type_ {
+                "T" => {
+                    let status = self.payload.first().copied().unwrap_or_default();
+                    if status & TEXT_RESERVED_MASK != 0 {
+                        let error = at(
+                            Error::InvalidTextStatus,
+                            self.payload_offset,
+                            Field::Payload,
+                        );
+                        if parser.options.is_enforced(Check::TextStatus) {
+                            return Err(error);
+                        }
+                        warning = Some(error);
+                    }
+                    decode_text(self.payload).map_err(|(error, offset)| {
+                        at(error, self.payload_offset + offset, Field::Payload)
+                    })?
+                }
+                "U" => {
+                    let Some((&prefix, uri)) = self.payload.split_first() else {
+                        return Err(at(
+                            Error::SliceTooShort,
+                            self.payload_offset,
+                            Field::Payload,
+                        ));
+                    };
+                    let uri = core::str::from_utf8(uri).map_err(|e| {
+                        at(
+                            e.into(),
+                            self.payload_offset + 1 + e.valid_up_to(),
+                            Field::Payload,
+                        )
+                    })?;
+                    RecordType::Uri {
+                        prefix,
+                        uri: borrowed(uri),
+                    }
+                }
+                "Sp" => RecordType::smart_poster(self.payload),
+                t => return Err(type_error(Error::UnsupportedRecordType(t.into()))),
+            }),
+            // Well-known types are matched case sensitively, and external
+            // types case insensitively.
+            TypeNameFormat::NfcExternal => match type_ {
+                #[cfg(feature = "cbor")]
+                _ if TypeNameFormat::NfcExternal.type_eq(type_, CBOR_TYPE) => {
+                    Payload::RTD(RecordType::cbor(self.payload))
+                }
+                _ => {
+                    if let Some(index) = type_.find(':') {
+                        let domain = &type_[..index];
+                        let type_ = &type_[index + 1..];
+                        check_external_type(domain, type_).map_err(type_error)?;
+                        Payload::RTD(RecordType::external(domain, type_, self.payload))
+                    } else {
+                        return Err(type_error(Error::InvalidExternalType(type_.into())));
+                    }
+                }
+            },
+            TypeNameFormat::Media => {
+                check_media_type(type_).map_err(type_error)?;
+                Payload::media(type_, self.payload)
+            }
+            TypeNameFormat::AbsoluteUri => Payload::absolute_uri(type_, self.payload),
+            TypeNameFormat::Empty | TypeNameFormat::Unknown if !type_.is_empty() => {
+                return Err(type_error(Error::NonEmptyField(Field::Type)));
+            }
+            TypeNameFormat::Empty => {
+                if self.id.is_some() {
+                    let id_offset = self.type_offset + self.type_.len();
+                    return Err(at(Error::NonEmptyField(Field::Id), id_offset, Field::Id));
+                }
+                if !self.payload.is_empty() {
+                    let error = Error::NonEmptyField(Field::Payload);
+                    return Err(at(error, self.payload_offset, Field::Payload));
+                }
+                Payload::Empty
+            }
+            TypeNameFormat::Unknown => Payload::unknown(self.payload),
+            tnf => {
+                return Err(at(
+                    Error::UnsupportedTypeNameFormat(tnf),
+                    self.header_offset,
+                    Field::Header,
+                ))
+            }
+        };
+        // The defect is only reported once the record is known to be kept.
+        if let Some(warning) = warning {
+            parser.report(Diagnostic::Accepted(warning));
+        }
+        Ok(Record {
+            id: self.id.map(Into::into),
+            payload,
+            chunk_size: None,
+        })
+    }
+}
+
+/// Payload of a chunked record being reassembled.
+#[cfg(feature = "alloc")]
+struct Chunked<'a> {
+    /// The initial chunk, which carries the type and ID.
+    first: RawRecord<'a>,
+    index: usize,
+    payload: Vec<u8>,
+    chunks: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> Chunked<'a> {
+    /// Adds the chunk `raw` to the payload being reassembled in `chunked`, or
+    /// starts one. Hands back the initial chunk and the whole payload once
+    /// `raw` is the last chunk.
+    fn push(
+        chunked: &mut Option<Self>,
+        raw: RawRecord<'a>,
+        index: usize,
+        limits: &Limits,
+    ) -> Result<Option<(RawRecord<'a>, usize, Vec<u8>)>, ParseError> {
+        let at = |error| ParseError::new(error, raw.header_offset, index, Field::Header);
+        let Some(mut chunks) = chunked.take() else {
+            // Only the initial chunk names a type.
+            if raw.header.type_name_format() == TypeNameFormat::Unchanged {
+                return Err(at(Error::InvalidChunk));
+            }
+            *chunked = Some(Chunked {
+                payload: raw.payload.to_vec(),
+                first: raw,
+                index,
+                chunks: 1,
+            });
+            return Ok(None);
+        };
+        // The following chunks carry nothing but payload.
+        if raw.header.type_name_format() != TypeNameFormat::Unchanged
+            || !raw.type_.is_empty()
+            || raw.id.is_some()
+        {
+            return Err(at(Error::InvalidChunk));
+        }
+        chunks.chunks += 1;
+        if chunks.chunks > limits.chunks {
+            return Err(at(Error::TooManyChunks));
+        }
+        if raw.payload.len() > limits.payload - chunks.payload.len() {
+            return Err(at(Error::PayloadTooLarge));
+        }
+        chunks.payload.extend_from_slice(raw.payload);
+        if raw.header.message_chunk() {
+            *chunked = Some(chunks);
+            return Ok(None);
+        }
+        Ok(Some((chunks.first, chunks.index, chunks.payload)))
+    }
+}
+
+/// Whether `error` is a bound of [`Limits`], which stops the parse in every
+/// mode.
+fn is_limit(error: &ParseError) -> bool {
+    matches!(
+        error.error(),
+        Error::TooManyRecords
+            | Error::PayloadTooLarge
+            | Error::TotalPayloadTooLarge
+            | Error::TooManyChunks
+    )
+}
+
+/// Options and diagnostics of a parse in progress.
+struct Parser<'o> {
+    options: &'o ParseOptions,
+    diagnostics: Diagnostics,
+}
+
+impl Parser<'_> {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        #[cfg(feature = "alloc")]
+        self.diagnostics.push(diagnostic);
+        // Past the capacity, diagnostics are dropped rather than failing a
+        // parse that is otherwise fine.
+        #[cfg(not(feature = "alloc"))]
+        let _ = self.diagnostics.push(diagnostic);
+    }
+
+    /// Refuses `error` when `check` is enforced, and reports it otherwise.
+    fn check(&mut self, check: Check, error: ParseError) -> Result<(), ParseError> {
+        if self.options.is_enforced(check) {
+            return Err(error);
+        }
+        self.report(Diagnostic::Accepted(error));
+        Ok(())
+    }
+
+    /// Hands back `error` when it must stop the parse, and reports it as a
+    /// skipped record otherwise.
+    fn skip(&mut self, error: ParseError) -> Result<(), ParseError> {
+        if self.options.mode != Mode::Recovery || is_limit(&error) {
+            return Err(error);
+        }
+        self.report(Diagnostic::Skipped(error));
+        Ok(())
+    }
+}
+
+impl<'a> Message<'a> {
+    /// Parses `slice` as configured by `options`. In [`Mode::Strict`] this is
+    /// [`Message::try_from`]; the other modes read past some defects and report
+    /// them along with the message.
+    pub fn parse(slice: &'a [u8], options: &ParseOptions) -> Result<Parsed<'a>, ParseError> {
+        let mut parser = Parser {
+            options,
+            diagnostics: Diagnostics::new(),
+        };
+        let mut reader = Reader::new(slice);
+        if slice.is_empty() {
+            return Err(reader.error(Error::SliceTooShort));
+        }
+        let limits = &options.limits;
+        let mut records = Vec::new();
+        let mut layout = Layouts::new();
+        let mut ended = false;
+        let mut header_offset = 0;
+        let mut total_payload = 0;
+        #[cfg(feature = "alloc")]
+        let mut chunked = None;
+        // Index of the record being read, whether it is kept or not.
+        let mut index = 0;
+        while reader.remaining() > 0 {
+            reader.record = index;
+            reader.enter(Field::Header);
+            // Nothing may follow the record that ended the message.
+            if ended {
+                parser.check(Check::TrailingData, reader.error(Error::InvalidFraming))?;
+                break;
+            }
+            if index >= limits.records {
+                return Err(reader.error(Error::TooManyRecords));
+            }
+            header_offset = reader.offset;
+            let raw = match RawRecord::read(&mut reader, limits, &mut total_payload) {
+                Ok(raw) => raw,
+                Err(error) => {
+                    // Nothing past a record that cannot be framed can be
+                    // framed either.
+                    parser.skip(error)?;
+                    ended = true;
+                    break;
+                }
+            };
+            // Only the first record begins the message.
+            if raw.header.message_begin() != (index == 0) {
+                let error =
+                    ParseError::new(Error::InvalidFraming, header_offset, index, Field::Header);
+                parser.check(Check::MessageBegin, error)?;
+            }
+            ended = raw.header.message_end();
+            #[cfg(feature = "alloc")]
+            if chunked.is_some() || raw.header.message_chunk() {
+                match Chunked::push(&mut chunked, raw, index, limits) {
+                    Ok(None) => {}
+                    Ok(Some((first, first_index, payload))) => {
+                        let chunk_size = NonZeroUsize::new(first.payload.len());
+                        let raw = RawRecord {
+                            payload: &payload,
+                            ..first
+                        };
+                        match raw.decode(first_index, &mut parser) {
+                            Ok(record) => {
+                                let mut record = record.into_owned();
+                                record.set_chunk_size(chunk_size);
+                                records.push(record);
+                                layout.push(RecordLayout {
+                                    offset: raw.header_offset,
+                                    header: raw.header.0,
+                                    chunks: index + 1 - first_index,
+                                });
+                            }
+                            Err(error) => parser.skip(error)?,
+                        }
+                    }
+                    Err(error) => parser.skip(error)?,
+                }
+                index += 1;
+                continue;
+            }
+            let record_layout = RecordLayout {
+                offset: header_offset,
+                header: raw.header.0,
+                chunks: 1,
+            };
+            match raw.decode(index, &mut parser) {
+                #[cfg(feature = "alloc")]
+                Ok(record) => {
+                    records.push(record);
+                    layout.push(record_layout);
+                }
+                #[cfg(not(feature = "alloc"))]
+                Ok(record) => {
+                    records.push(record).map_err(|_| {
+                        ParseError::new(Error::TooManyRecords, header_offset, index, Field::Header)
+                    })?;
+                    // the layout holds as many entries as the records
+                    let _ = layout.push(record_layout);
+                }
+                Err(error) => parser.skip(error)?,
+            }
+            index += 1;
+        }
+        // The message cannot end in the middle of a chunked payload.
+        #[cfg(feature = "alloc")]
+        if let Some(chunks) = chunked {
+            let error = ParseError::new(
+                Error::InvalidChunk,
+                chunks.first.header_offset,
+                chunks.index,
+                Field::Header,
+            );
+            parser.skip(error)?;
+        }
+        // The last record has to end the message.
+        if !ended {
+            let error = ParseError::new(
+                Error::InvalidFraming,
+                header_offset,
+                index - 1,
+                Field::Header,
+            );
+            parser.check(Check::MessageEnd, error)?;
+        }
+        Ok(Parsed {
+            message: Message { records },
+            diagnostics: parser.diagnostics,
+            layout,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Message<'a> {
+    type Error = ParseError;
+
+    fn try_from(slice: &'a [u8]) -> Result<Self, ParseError> {
+        Message::parse(slice, &ParseOptions::default()).map(|parsed| parsed.message)
+    }
+}
+
+/// Decodes the payload of a Text record. An error comes with the offset in the
+/// payload of the byte it was found at.
+fn decode_text(data: &[u8]) -> Result<RecordType<'_>, (Error, usize)> {
+    let Some(&status) = data.first() else {
+        return Err((Error::SliceTooShort, 0));
+    };
+    let enc_len = (status & TEXT_LANGUAGE_LEN_MASK) as usize;
+    let is_utf16 = (status & TEXT_UTF16_MASK) != 0;
+    if enc_len == 0 {
+        return Err((Error::InvalidLanguageCode, 0));
+    }
+    if data.len() < enc_len + 1 {
+        return Err((Error::SliceTooShort, 0));
+    }
+    let enc = core::str::from_utf8(&data[1..enc_len + 1])
+        .map_err(|e| (Error::UTF8(e), 1 + e.valid_up_to()))?;
+    if !enc.is_ascii() {
+        return Err((Error::InvalidLanguageCode, 1));
+    }
+    let txt_offset = enc_len + 1;
+    let txt = if is_utf16 {
+        #[cfg(not(feature = "alloc"))]
+        return Err((Error::UnsupportedEncoding, 0));
+        #[cfg(feature = "alloc")]
+        Cow::Owned(decode_utf16(&data[txt_offset..], false).map_err(|error| (error, txt_offset))?)
+    } else {
+        borrowed(
+            core::str::from_utf8(&data[txt_offset..])
+                .map_err(|e| (Error::UTF8(e), txt_offset + e.valid_up_to()))?,
+        )
+    };
+    Ok(RecordType::Text {
+        enc: borrowed(enc),
+        txt,
+    })
+}
+
+/// Decodes UTF-16 text. A byte order mark chooses the endianness and is not
+/// part of the text; without one the text is little endian if
+/// `little_endian` is set, big endian otherwise.
+#[cfg(feature = "alloc")]
+pub(crate) fn decode_utf16(mut bytes: &[u8], mut little_endian: bool) -> Result<String, Error> {
+    // Ensure the byte slice has an even length (UTF-16 is 2 bytes per unit)
+    if bytes.len() % 2 != 0 {
+        return Err(Error::UTF16OddLength(bytes.len()));
+    }
+    match bytes.get(..2) {
+        Some([0xFF, 0xFE]) => {
+            bytes = &bytes[2..];
+            little_endian = true;
+        }
+        Some([0xFE, 0xFF]) => {
+            bytes = &bytes[2..];
+            little_endian = false;
+        }
+        _ => {}
+    }
+    // Convert the byte slice into u16 units
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|chunk| {
+            let unit = [chunk[0], chunk[1]];
+            if little_endian {
+                u16::from_le_bytes(unit)
+            } else {
+                u16::from_be_bytes(unit)
+            }
+        })
+        .collect();
+    String::from_utf16(&units).map_err(|_| Error::UTF16Decode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One Text record, header flags aside.
+    fn text(header: u8) -> [u8; 8] {
+        [header, 0x01, 0x04, b'T', 0x02, b'f', b'r', b'x']
+    }
+
+    /// The defects a buggy writer leaves behind are refused by default, and
+    /// read past with a warning when asked.
+    #[test]
+    fn test_lenient_checks() {
+        let mut trailing = [0u8; 9];
+        trailing[..8].copy_from_slice(&text(0xD1));
+        let mut reserved = text(0xD1);
+        reserved[4] |= TEXT_RESERVED_MASK;
+        let cases: [(&[u8], usize); 4] = [
+            (&trailing, 8),
+            (&text(0x91), 0),
+            (&text(0x51), 0),
+            (&reserved, 4),
+        ];
+        for (raw, offset) in cases {
+            let strict = Message::try_from(raw).unwrap_err();
+            assert_eq!(
+                Message::parse(raw, &ParseOptions::default()).unwrap_err(),
+                strict
+            );
+
+            let parsed = Message::parse(raw, &ParseOptions::new(Mode::Lenient)).unwrap();
+            assert_eq!(parsed.message.records().len(), 1);
+            assert_eq!(parsed.diagnostics.len(), 1);
+            assert_eq!(parsed.diagnostics[0], Diagnostic::Accepted(strict));
+            assert_eq!(parsed.diagnostics[0].error().offset(), offset);
+        }
+
+        let options = ParseOptions::new(Mode::Lenient).enforce(Check::TrailingData);
+        assert_eq!(
+            Message::parse(&trailing, &options).unwrap_err().error(),
+            &Error::InvalidFraming
+        );
+    }
+
+    /// Recovery keeps the records around one that cannot be decoded, and the
+    /// ones before bytes that cannot be framed.
+    #[test]
+    fn test_recovery_skips_damaged_records() {
+        let mut raw = [0u8; 24];
+        raw[..8].copy_from_slice(&text(0x91));
+        // a well-known type the crate does not know
+        raw[8..16].copy_from_slice(&text(0x11));
+        raw[11] = b'X';
+        raw[16..].copy_from_slice(&text(0x51));
+
+        assert_eq!(
+            Message::parse(&raw, &ParseOptions::new(Mode::Lenient))
+                .unwrap_err()
+                .error(),
+            &Error::UnsupportedRecordType("X".into())
+        );
+        let parsed = Message::parse(&raw, &ParseOptions::new(Mode::Recovery)).unwrap();
+        assert_eq!(parsed.message.records().len(), 2);
+        assert!(matches!(
+            &parsed.diagnostics[..],
+            [Diagnostic::Skipped(error)] if error.record() == 1 && error.field() == Field::Type
+        ));
+
+        // the last record is cut in its payload
+        let parsed = Message::parse(&raw[..20], &ParseOptions::new(Mode::Recovery)).unwrap();
+        assert_eq!(parsed.message.records().len(), 1);
+        assert_eq!(parsed.diagnostics.len(), 2);
+        assert!(matches!(
+            &parsed.diagnostics[1],
+            Diagnostic::Skipped(error) if error.field() == Field::Payload
+        ));
+    }
+
+    /// A Smart Poster keeps its nested message serialized, and an absolute
+    /// URI names the type of its payload.
+    #[test]
+    fn test_smart_poster_and_absolute_uri() {
+        let raw = [
+            0x91, 0x02, 0x05, b'S', b'p', 0xD1, 0x01, 0x01, b'U', 0x00, 0x53, 0x05, 0x01, b'u',
+            b'r', b'n', b':', b'x', b'a',
+        ];
+        let msg = Message::try_from(raw.as_slice()).unwrap();
+        assert_eq!(
+            msg.records()[0].payload,
+            Payload::RTD(RecordType::smart_poster(&raw[5..10]))
+        );
+        assert_eq!(
+            msg.records()[1].payload,
+            Payload::absolute_uri("urn:x", b"a")
+        );
+        assert_eq!(msg.to_vec().unwrap(), raw);
+    }
+
+    /// Empty and Unknown records carry no type, and an Empty record nothing at
+    /// all.
+    #[test]
+    fn test_empty_and_unknown_records() {
+        let raw = [0x90, 0x00, 0x00, 0x55, 0x00, 0x02, b'a', b'b'];
+        let msg = Message::try_from(&raw[..]).unwrap();
+        assert_eq!(msg.records()[0].payload, Payload::Empty);
+        assert_eq!(msg.records()[1].payload, Payload::unknown(b"ab"));
+        assert_eq!(&msg.to_vec().unwrap()[..], &raw[..]);
+
+        let cases: [(&[u8], Field, usize); 3] = [
+            (&[0xD5, 0x01, 0x00, b'X'], Field::Type, 3),
+            (&[0xD8, 0x00, 0x00, 0x01, b'i'], Field::Id, 4),
+            (&[0xD0, 0x00, 0x01, b'p'], Field::Payload, 3),
+        ];
+        for (raw, field, offset) in cases {
+            let error = Message::try_from(raw).unwrap_err();
+            assert_eq!(error.error(), &Error::NonEmptyField(field));
+            assert_eq!(error.offset(), offset);
+        }
+    }
+
+    /// Each bound refuses the input with its own error, whatever the mode.
+    #[test]
+    fn test_limits() {
+        let mut raw = [0u8; 16];
+        raw[..8].copy_from_slice(&text(0x91));
+        raw[8..].copy_from_slice(&text(0x51));
+        let parse = |limits: Limits| {
+            let options = ParseOptions::new(Mode::Recovery).with_limits(limits);
+            Message::parse(&raw, &options).map(|parsed| parsed.message.records().len())
+        };
+        assert_eq!(parse(Limits::default()), Ok(2));
+        let cases = [
+            (
+                Limits {
+                    records: 1,
+                    ..Limits::NONE
+                },
+                Error::TooManyRecords,
+                8,
+                Field::Header,
+            ),
+            (
+                Limits {
+                    payload: 3,
+                    ..Limits::NONE
+                },
+                Error::PayloadTooLarge,
+                2,
+                Field::PayloadLength,
+            ),
+            (
+                Limits {
+                    total_payload: 7,
+                    ..Limits::NONE
+                },
+                Error::TotalPayloadTooLarge,
+                10,
+                Field::PayloadLength,
+            ),
+        ];
+        for (limits, error, offset, field) in cases {
+            let err = parse(limits).unwrap_err();
+            assert_eq!(err.error(), &error);
+            assert_eq!((err.offset(), err.field()), (offset, field));
+        }
+    }
+
+    /// Without `alloc`, a message that outgrows its 8 records is refused as
+    /// having too many.
+    #[test]
+    #[cfg(not(feature = "alloc"))]
+    fn test_too_many_records_without_alloc() {
+        let mut raw = [0; 9 * 3];
+        for record in raw.chunks_mut(3) {
+            record[0] = 0x10;
+        }
+        raw[0] |= 0x80;
+        raw[24] |= 0x40;
+        let err = Message::try_from(raw.as_slice()).unwrap_err();
+        assert_eq!(err.error(), &Error::TooManyRecords);
+        assert_eq!((err.offset(), err.record()), (24, 8));
+    }
+
+    /// A payload split into chunks is read back as one record.
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_chunked_payload() {
+        let raw = [
+            0xB4, 0x08, 0x02, b'e', b'x', b'.', b'c', b'o', b'm', b':', b't', b'a',
+            b'b', // initial
+            0x36, 0x00, 0x02, b'c', b'd', // middle
+            0x56, 0x00, 0x01, b'e', // last
+        ];
+        let msg = Message::try_from(raw.as_slice()).unwrap();
+        assert_eq!(msg.records().len(), 1);
+        assert_eq!(
+            msg.records()[0].payload,
+            Payload::RTD(RecordType::external("ex.com", "t", b"abcde"))
+        );
+
+        let limits = Limits {
+            chunks: 2,
+            ..Limits::NONE
+        };
+        let err = Message::parse(&raw, &ParseOptions::default().with_limits(limits)).unwrap_err();
+        assert_eq!(err.error(), &Error::TooManyChunks);
+        assert_eq!((err.offset(), err.record()), (18, 2));
+
+        // the middle chunk names a type of its own
+        let mut broken = raw;
+        broken[13] = 0x34;
+        assert_eq!(
+            Message::try_from(broken.as_slice()).unwrap_err().error(),
+            &Error::InvalidChunk
+        );
+        // the message ends with a chunk still to come
+        assert_eq!(
+            Message::try_from(&raw[..18]).unwrap_err().error(),
+            &Error::InvalidChunk
+        );
+    }
+}
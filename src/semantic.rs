@@ -0,0 +1,285 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(feature = "alloc")]
+use alloc::borrow::Cow;
+
+#[cfg(feature = "cbor")]
+use crate::CBOR_TYPE;
+use crate::{Message, Payload, Record, RecordType, Str, TypeNameFormat, URI_PREFIXES};
+
+/// Type name, ID and payload of an external record, the CBOR record being one.
+fn external<'r>(rtd: &'r RecordType<'_>) -> Option<(&'r str, &'r str, &'r [u8])> {
+    match rtd {
+        RecordType::External {
+            domain,
+            type_,
+            data,
+        } => Some((domain, type_, data)),
+        #[cfg(feature = "cbor")]
+        RecordType::Cbor(data) => CBOR_TYPE
+            .split_once(':')
+            .map(|(domain, type_)| (domain, type_, &data[..])),
+        _ => None,
+    }
+}
+
+/// Drops the first `len` bytes of `text`, keeping it borrowed if it was.
+fn drop_start(text: &mut Str<'_>, len: usize) {
+    #[cfg(feature = "alloc")]
+    match text {
+        Cow::Borrowed(borrowed) => *borrowed = &borrowed[len..],
+        Cow::Owned(owned) => {
+            owned.drain(..len);
+        }
+    }
+    #[cfg(not(feature = "alloc"))]
+    {
+        *text = &text[len..];
+    }
+}
+
+/// Lowers the case of the first `len` bytes of `text`, which only allocates
+/// when one of them is upper case.
+#[cfg(feature = "alloc")]
+fn lowercase(text: &mut Str<'_>, len: usize) {
+    if text[..len].bytes().any(|byte| byte.is_ascii_uppercase()) {
+        text.to_mut()[..len].make_ascii_lowercase();
+    }
+}
+
+impl Record<'_> {
+    /// Whether the two records mean the same, however they are encoded. The
+    /// comparison sees through what the wire format leaves to the writer:
+    ///
+    /// - the short or normal form of the record, and its chunks;
+    /// - the prefix a URI is abbreviated by;
+    /// - the encoding of a Text record, whose language code compares case
+    ///   insensitively like any IANA language tag;
+    /// - the case of an external type name, and of the type and subtype of a
+    ///   media type.
+    ///
+    /// IDs and payload data compare byte for byte.
+    pub fn semantic_eq(&self, other: &Record<'_>) -> bool {
+        if self.id() != other.id() {
+            return false;
+        }
+        match (&self.payload, &other.payload) {
+            (
+                Payload::RTD(RecordType::Text { enc, txt }),
+                Payload::RTD(RecordType::Text {
+                    enc: other_enc,
+                    txt: other_txt,
+                }),
+            ) => enc.eq_ignore_ascii_case(other_enc) && txt == other_txt,
+            (
+                Payload::RTD(RecordType::Uri { prefix, uri }),
+                Payload::RTD(RecordType::Uri {
+                    prefix: other_prefix,
+                    uri: other_uri,
+                }),
+            ) => {
+                let full = RecordType::uri_prefix(*prefix).bytes().chain(uri.bytes());
+                full.eq(RecordType::uri_prefix(*other_prefix)
+                    .bytes()
+                    .chain(other_uri.bytes()))
+            }
+            (
+                Payload::RTD(RecordType::SmartPoster(ndef)),
+                Payload::RTD(RecordType::SmartPoster(other_ndef)),
+            ) => ndef == other_ndef,
+            (Payload::RTD(rtd), Payload::RTD(other_rtd)) => {
+                match (external(rtd), external(other_rtd)) {
+                    (Some((domain, type_, data)), Some((other_domain, other_type, other_data))) => {
+                        let external = TypeNameFormat::NfcExternal;
+                        external.type_eq(domain, other_domain)
+                            && external.type_eq(type_, other_type)
+                            && data == other_data
+                    }
+                    _ => false,
+                }
+            }
+            (
+                Payload::Media { type_, data },
+                Payload::Media {
+                    type_: other_type,
+                    data: other_data,
+                },
+            ) => TypeNameFormat::Media.type_eq(type_, other_type) && data == other_data,
+            (
+                Payload::AbsoluteUri { uri, data },
+                Payload::AbsoluteUri {
+                    uri: other_uri,
+                    data: other_data,
+                },
+            ) => TypeNameFormat::AbsoluteUri.type_eq(uri, other_uri) && data == other_data,
+            (Payload::Empty, Payload::Empty) => true,
+            (Payload::Unknown(data), Payload::Unknown(other_data)) => data == other_data,
+            _ => false,
+        }
+    }
+
+    /// Rewrites the record into the encoding [`Record::semantic_eq`] sees
+    /// through that this crate prefers: a URI is abbreviated by its longest
+    /// prefix and the payload is written whole. Text is always written in
+    /// UTF-8 and a record in its short form when it fits.
+    ///
+    /// With `alloc`, the language code of a Text record, an external type
+    /// name and the type and subtype of a media type are also lower cased.
+    /// Without it they keep their case, which would take an allocation to
+    /// change.
+    pub fn canonicalize(&mut self) {
+        self.set_chunk_size(None);
+        #[cfg(feature = "alloc")]
+        match &mut self.payload {
+            Payload::RTD(RecordType::Text { enc, .. }) => lowercase(enc, enc.len()),
+            Payload::RTD(RecordType::External { domain, type_, .. }) => {
+                lowercase(domain, domain.len());
+                lowercase(type_, type_.len());
+            }
+            Payload::Media { type_, .. } => {
+                let len = type_.find(';').unwrap_or(type_.len());
+                lowercase(type_, len);
+            }
+            _ => {}
+        }
+        if let Payload::RTD(RecordType::Uri { prefix, uri }) = &mut self.payload {
+            let current = RecordType::uri_prefix(*prefix);
+            // The longest prefix of the whole URI extends the current one, so
+            // the rest shrinks and can keep borrowing.
+            let mut best = None;
+            for (code, abbreviated) in URI_PREFIXES.iter().enumerate() {
+                let extends = abbreviated
+                    .strip_prefix(current)
+                    .is_some_and(|extra| uri.starts_with(extra));
+                if extends && best.map_or(true, |(_, len)| abbreviated.len() > len) {
+                    best = Some((code, abbreviated.len()));
+                }
+            }
+            if let Some((code, len)) = best {
+                drop_start(uri, len - current.len());
+                *prefix = code as u8;
+            }
+        }
+    }
+}
+
+impl Message<'_> {
+    /// Whether the two messages hold records meaning the same, in the same
+    /// order, see [`Record::semantic_eq`].
+    pub fn semantic_eq(&self, other: &Message<'_>) -> bool {
+        self.records.len() == other.records.len()
+            && self
+                .records
+                .iter()
+                .zip(other.records.iter())
+                .all(|(record, other)| record.semantic_eq(other))
+    }
+
+    /// Rewrites every record into the preferred encoding, see
+    /// [`Record::canonicalize`]. Two messages meaning the same then serialize
+    /// to the same bytes, or without `alloc` to the same bytes up to the case
+    /// of their names.
+    pub fn canonicalize(&mut self) {
+        for record in self.records.iter_mut() {
+            record.canonicalize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::borrowed;
+
+    /// Encodings of the same content compare equal and canonicalize to the
+    /// same bytes.
+    #[test]
+    fn test_semantic_eq() {
+        // normal form, unabbreviated URI, upper case external type
+        let raw = [
+            0x81, 0x01, 0x00, 0x00, 0x00, 0x0D, b'U', 0x00, b'h', b't', b't', b'p', b':', b'/',
+            b'/', b'w', b'w', b'w', b'.', b'x', 0x54, 0x08, 0x01, b'E', b'X', b'.', b'c', b'o',
+            b'm', b':', b'T', b'a',
+        ];
+        let mut msg = Message::try_from(raw.as_slice()).unwrap();
+        let expected = Message::builder()
+            .uri("http://www.x")
+            .external("ex.com", "t", b"a")
+            .build()
+            .unwrap();
+        assert_ne!(msg, expected);
+        assert!(msg.semantic_eq(&expected));
+
+        msg.canonicalize();
+        assert_eq!(
+            msg.records[0].payload,
+            Payload::RTD(RecordType::Uri {
+                prefix: 1,
+                uri: borrowed("x"),
+            })
+        );
+        let bytes = msg.to_vec().unwrap();
+        assert_eq!(&bytes[..5], [0x91, 0x01, 0x02, b'U', 0x01]);
+        #[cfg(feature = "alloc")]
+        assert_eq!(bytes, expected.to_vec().unwrap());
+        // without an allocation the external type keeps its case
+        #[cfg(not(feature = "alloc"))]
+        assert_eq!(
+            bytes,
+            Message::builder()
+                .uri("http://www.x")
+                .external("EX.com", "T", b"a")
+                .build()
+                .unwrap()
+                .to_vec()
+                .unwrap()
+        );
+
+        let other = Message::builder()
+            .uri("http://www.y")
+            .external("ex.com", "t", b"a")
+            .build()
+            .unwrap();
+        assert!(!msg.semantic_eq(&other));
+        assert!(!msg.semantic_eq(&Message::default()));
+        assert!(Record::new(None, Payload::media("Text/Plain;a=B", b""))
+            .semantic_eq(&Record::new(None, Payload::media("text/plain;a=B", b""))));
+    }
+
+    /// UTF-16 text means the same as its UTF-8 encoding.
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_semantic_eq_utf16() {
+        let utf16 = [
+            0xD1, 0x01, 0x07, b'T', 0x82, b'E', b'N', 0xFE, 0xFF, 0x00, b'a',
+        ];
+        let utf8 = [0xD1, 0x01, 0x04, b'T', 0x02, b'e', b'n', b'a'];
+        let utf16 = Message::try_from(utf16.as_slice()).unwrap();
+        assert!(utf16.semantic_eq(&Message::try_from(utf8.as_slice()).unwrap()));
+        assert_eq!(utf16.to_vec().unwrap()[4..], [0x02, b'E', b'N', b'a']);
+    }
+
+    /// Names are lower cased, media type parameters excepted.
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_canonicalize_case() {
+        let mut msg = Message::builder()
+            .text("EN-us", "Hi")
+            .media("Text/Plain;charset=UTF-8", b"a")
+            .build()
+            .unwrap();
+        msg.canonicalize();
+        assert_eq!(
+            msg.records[0].payload,
+            Payload::RTD(RecordType::Text {
+                enc: borrowed("en-us"),
+                txt: borrowed("Hi"),
+            })
+        );
+        assert_eq!(
+            msg.records[1].payload,
+            Payload::media("text/plain;charset=UTF-8", b"a")
+        );
+    }
+}
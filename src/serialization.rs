@@ -0,0 +1,308 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+    vec::Vec,
+};
+use core::{fmt::Write, num::NonZeroUsize};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+#[cfg(feature = "cbor")]
+use crate::CBOR_TYPE;
+use crate::{
+    check_external_type, check_language, check_media_type, uri_prefix_code, Error, Field, Payload,
+    Record, RecordType, TypeNameFormat, URI_PREFIXES,
+};
+
+/// Bytes written as a string of lower case hex digits.
+struct Hex<'a>(Cow<'a, [u8]>);
+
+impl Serialize for Hex<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut hex = String::with_capacity(2 * self.0.len());
+        for byte in self.0.iter() {
+            let _ = write!(hex, "{byte:02x}");
+        }
+        serializer.serialize_str(&hex)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hex<'_> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = Cow::<str>::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(de::Error::invalid_length(
+                hex.len(),
+                &"an even number of digits",
+            ));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Str(&hex), &"hex"))
+            })
+            .collect::<Result<Vec<u8>, _>>()
+            .map(|bytes| Hex(Cow::Owned(bytes)))
+    }
+}
+
+/// Readable form of a payload: its type name format and type, then the
+/// decoded text of a Text record, the whole URI of a URI record, or the
+/// payload in hex for any other record.
+#[derive(Serialize, Deserialize)]
+struct PayloadRepr<'a> {
+    tnf: TypeNameFormat,
+    #[serde(rename = "type")]
+    type_: Cow<'a, str>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lang: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uri: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payload: Option<Hex<'a>>,
+}
+
+impl<'a> PayloadRepr<'a> {
+    fn new(payload: &'a Payload<'_>) -> Result<Self, Error> {
+        let mut repr = PayloadRepr {
+            tnf: TypeNameFormat::from(payload),
+            type_: Cow::Owned(payload.type_name()?.as_str().to_owned()),
+            lang: None,
+            text: None,
+            uri: None,
+            payload: None,
+        };
+        match payload {
+            Payload::RTD(RecordType::Text { enc, txt }) => {
+                repr.lang = Some(Cow::Borrowed(enc));
+                repr.text = Some(Cow::Borrowed(txt));
+            }
+            Payload::RTD(RecordType::Uri { prefix, uri }) => {
+                repr.uri = Some(Cow::Owned(RecordType::uri_prefix(*prefix).to_owned() + uri));
+            }
+            Payload::RTD(RecordType::External { data, .. } | RecordType::SmartPoster(data))
+            | Payload::Media { data, .. }
+            | Payload::AbsoluteUri { data, .. }
+            | Payload::Unknown(data) => repr.payload = Some(Hex(Cow::Borrowed(data))),
+            Payload::Empty => {}
+            #[cfg(feature = "cbor")]
+            Payload::RTD(RecordType::Cbor(data)) => repr.payload = Some(Hex(Cow::Borrowed(data))),
+        }
+        Ok(repr)
+    }
+
+    /// The payload described, checked like the parser checks a record. A
+    /// field the record type needs is reported missing.
+    fn payload<E: de::Error>(self) -> Result<Payload<'static>, E> {
+        let type_ = self.type_.into_owned();
+        let data = || {
+            self.payload
+                .map(|hex| hex.0.into_owned())
+                .unwrap_or_default()
+        };
+        Ok(match self.tnf {
+            TypeNameFormat::NfcWellKnown => Payload::RTD(match type_.as_str() {
+                "T" => {
+                    let enc = self.lang.ok_or_else(|| E::missing_field("lang"))?;
+                    let txt = self.text.ok_or_else(|| E::missing_field("text"))?;
+                    check_language(&enc).map_err(E::custom)?;
+                    RecordType::Text {
+                        enc: Cow::Owned(enc.into_owned()),
+                        txt: Cow::Owned(txt.into_owned()),
+                    }
+                }
+                "U" => {
+                    let uri = self.uri.ok_or_else(|| E::missing_field("uri"))?;
+                    let prefix = uri_prefix_code(uri.as_bytes());
+                    RecordType::Uri {
+                        prefix: prefix as u8,
+                        uri: Cow::Owned(uri[URI_PREFIXES[prefix].len()..].to_owned()),
+                    }
+                }
+                "Sp" => RecordType::SmartPoster(Cow::Owned(data())),
+                _ => {
+                    return Err(E::custom(Error::UnsupportedRecordType(
+                        type_.as_str().into(),
+                    )))
+                }
+            }),
+            TypeNameFormat::NfcExternal => {
+                #[cfg(feature = "cbor")]
+                if TypeNameFormat::NfcExternal.type_eq(&type_, CBOR_TYPE) {
+                    return Ok(Payload::RTD(RecordType::Cbor(Cow::Owned(data()))));
+                }
+                let Some((domain, name)) = type_.split_once(':') else {
+                    return Err(E::custom(Error::InvalidExternalType(type_.as_str().into())));
+                };
+                check_external_type(domain, name).map_err(E::custom)?;
+                Payload::RTD(RecordType::External {
+                    domain: Cow::Owned(domain.to_owned()),
+                    type_: Cow::Owned(name.to_owned()),
+                    data: Cow::Owned(data()),
+                })
+            }
+            TypeNameFormat::Media => {
+                check_media_type(&type_).map_err(E::custom)?;
+                Payload::Media {
+                    data: Cow::Owned(data()),
+                    type_: Cow::Owned(type_),
+                }
+            }
+            TypeNameFormat::AbsoluteUri => Payload::AbsoluteUri {
+                data: Cow::Owned(data()),
+                uri: Cow::Owned(type_),
+            },
+            TypeNameFormat::Empty | TypeNameFormat::Unknown if !type_.is_empty() => {
+                return Err(E::custom(Error::NonEmptyField(Field::Type)))
+            }
+            TypeNameFormat::Empty => Payload::Empty,
+            TypeNameFormat::Unknown => Payload::Unknown(Cow::Owned(data())),
+            tnf => return Err(E::custom(Error::UnsupportedTypeNameFormat(tnf))),
+        })
+    }
+}
+
+impl Serialize for Payload<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PayloadRepr::new(self)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload<'_> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PayloadRepr::deserialize(deserializer)?.payload()
+    }
+}
+
+/// A record type is written as the payload it makes.
+impl Serialize for RecordType<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Payload::RTD(self.clone()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RecordType<'_> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Payload::deserialize(deserializer)? {
+            Payload::RTD(rtd) => Ok(rtd),
+            payload => Err(de::Error::custom(Error::UnsupportedTypeNameFormat(
+                TypeNameFormat::from(&payload),
+            ))),
+        }
+    }
+}
+
+/// Readable form of a record: its ID in hex, if any, along with its payload
+/// and chunk size.
+#[derive(Serialize, Deserialize)]
+struct RecordRepr<'a, P> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<Hex<'a>>,
+    #[serde(flatten)]
+    payload: P,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chunk_size: Option<NonZeroUsize>,
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RecordRepr {
+            id: self.id().map(|id| Hex(Cow::Borrowed(id))),
+            payload: &self.payload,
+            chunk_size: self.chunk_size,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// The record is checked as a whole once read, so that one `to_vec` would
+/// refuse, such as an empty record with an ID, is refused here already.
+impl<'de> Deserialize<'de> for Record<'_> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = RecordRepr::<Payload>::deserialize(deserializer)?;
+        let record = Record {
+            id: repr.id.map(|id| id.0),
+            payload: repr.payload,
+            chunk_size: repr.chunk_size,
+        };
+        let mut checked = Ok(());
+        record.check(&mut |error| {
+            if checked.is_ok() {
+                checked = Err(de::Error::custom(error));
+            }
+        });
+        checked.map(|()| record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use core::num::NonZeroUsize;
+
+    use crate::Message;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let msg = Message::builder()
+            .text("en", "hi")
+            .id(b"t\x01")
+            .uri("https://www.example.com")
+            .external("ex.com", "t", b"\x00\xff")
+            .chunk_size(NonZeroUsize::new(1).unwrap())
+            .media("text/plain", b"x")
+            .build()
+            .unwrap();
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "records": [
+                { "id": "7401", "tnf": "well-known", "type": "T", "lang": "en", "text": "hi" },
+                { "tnf": "well-known", "type": "U", "uri": "https://www.example.com" },
+                { "tnf": "external", "type": "ex.com:t", "payload": "00ff", "chunk_size": 1 },
+                { "tnf": "media", "type": "text/plain", "payload": "78" },
+            ]})
+        );
+        let back: Message = serde_json::from_value(json).unwrap();
+        assert_eq!(back, msg);
+    }
+
+    /// Deserialized records are checked like parsed ones.
+    #[test]
+    fn test_serde_checks_records() {
+        let parse = |json| {
+            serde_json::from_str::<Message>(json)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(parse(r#"{"records":[{"tnf":"external","type":"ex.com"}]}"#)
+            .starts_with("invalid external type name `ex.com`"));
+        assert!(
+            parse(r#"{"records":[{"tnf":"well-known","type":"T","text":"x"}]}"#)
+                .starts_with("missing field `lang`")
+        );
+        assert!(
+            parse(r#"{"records":[{"tnf":"well-known","type":"T","lang":"en"}]}"#)
+                .starts_with("missing field `text`")
+        );
+        assert!(parse(r#"{"records":[{"tnf":"well-known","type":"Hc"}]}"#)
+            .starts_with("record type `Hc` is not supported"));
+        assert!(
+            parse(r#"{"records":[{"tnf":"media","type":"text/plain","payload":"7"}]}"#)
+                .starts_with("invalid length 1")
+        );
+        assert!(
+            parse(r#"{"records":[{"id":"01","tnf":"empty","type":""}]}"#)
+                .starts_with("ID must be empty under this type name format")
+        );
+    }
+}
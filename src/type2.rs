@@ -0,0 +1,518 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Data area of an NFC Forum Type 2 Tag, such as an NTAG or a MIFARE
+//! Ultralight, which holds the NDEF message in a TLV along with the TLVs
+//! describing the lock bytes and reserved memory.
+//!
+//! The functions of this module take the data area, which starts at byte
+//! [`DATA_AREA_START`] of the tag memory. Lock and Memory Control TLVs give the
+//! address of their area in the tag memory; those bytes are not part of any
+//! TLV, and are skipped when a TLV is read or written across them.
+//!
+//! ```
+//! use ndef::{type2, Message};
+//!
+//! let mut data = [0; 48];
+//! let msg = Message::builder().text("en", "hi").build().unwrap();
+//! type2::write_message(&mut data, &msg).unwrap();
+//!
+//! let ndef = type2::read_ndef(&data).unwrap();
+//! assert_eq!(Message::try_from(&ndef[..]).unwrap(), msg);
+//! ```
+//!
+//! [`VirtualTag`] holds the whole memory of a tag, to try out formatting,
+//! writing and locking without one.
+
+use core::ops::Range;
+
+use crate::{write_u8, Buffer, Error, Message, Result};
+
+mod tag;
+
+pub use tag::VirtualTag;
+
+/// Bytes of a page, the unit a Type 2 Tag is written in.
+pub const PAGE_LEN: usize = 4;
+
+/// Byte of the tag memory the data area starts at, after the serial number,
+/// the static lock bytes and the capability container.
+pub const DATA_AREA_START: usize = 16;
+
+/// Longest data area a capability container can announce.
+pub const MAX_DATA_AREA: usize = 8 * u8::MAX as usize;
+
+/// First byte of a capability container.
+const MAGIC: u8 = 0xE1;
+
+pub(crate) const NULL: u8 = 0x00;
+const LOCK_CONTROL: u8 = 0x01;
+const MEMORY_CONTROL: u8 = 0x02;
+pub(crate) const NDEF_MESSAGE: u8 = 0x03;
+const PROPRIETARY: u8 = 0xFD;
+pub(crate) const TERMINATOR: u8 = 0xFE;
+
+/// First byte of a length written in three bytes.
+pub(crate) const LONG_LENGTH: u8 = 0xFF;
+
+/// Reserved areas a data area can describe.
+const MAX_AREAS: usize = 8;
+
+/// Value of a Lock Control or Memory Control TLV, which places an area of the
+/// tag memory in pages of `2^bytes_per_page` bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ControlTlv {
+    /// Page the area starts in, 4 bits.
+    pub page_address: u8,
+    /// Byte of that page the area starts at, 4 bits.
+    pub byte_offset: u8,
+    /// Lock bits of a lock area, bytes of a memory area; 0 stands for 256.
+    pub size: u8,
+    /// Bytes a lock bit locks, as a power of two, 4 bits. Unused by a memory
+    /// area.
+    pub bytes_locked_per_bit: u8,
+    /// Bytes in a page, as a power of two, 4 bits.
+    pub bytes_per_page: u8,
+}
+
+impl ControlTlv {
+    pub fn from_bytes(value: [u8; 3]) -> Self {
+        ControlTlv {
+            page_address: value[0] >> 4,
+            byte_offset: value[0] & 0x0F,
+            size: value[1],
+            bytes_locked_per_bit: value[2] >> 4,
+            bytes_per_page: value[2] & 0x0F,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [
+            (self.page_address << 4) | (self.byte_offset & 0x0F),
+            self.size,
+            (self.bytes_locked_per_bit << 4) | (self.bytes_per_page & 0x0F),
+        ]
+    }
+
+    /// Byte of the tag memory the area starts at.
+    pub fn byte_address(&self) -> usize {
+        ((self.page_address as usize) << self.bytes_per_page) + self.byte_offset as usize
+    }
+
+    fn size(&self) -> usize {
+        match self.size {
+            0 => 256,
+            size => size as usize,
+        }
+    }
+
+    /// Bytes of the tag memory holding the lock bits, when this is the value
+    /// of a Lock Control TLV.
+    pub fn lock_area(&self) -> Range<usize> {
+        let start = self.byte_address();
+        start..start + self.size().div_ceil(8)
+    }
+
+    /// Bytes of the tag memory reserved, when this is the value of a Memory
+    /// Control TLV.
+    pub fn memory_area(&self) -> Range<usize> {
+        let start = self.byte_address();
+        start..start + self.size()
+    }
+
+    /// Bytes a lock bit locks, when this is the value of a Lock Control TLV.
+    pub fn bytes_locked_per_bit(&self) -> usize {
+        1 << self.bytes_locked_per_bit
+    }
+}
+
+/// Capability container of a Type 2 Tag, in page 3 of its memory, which
+/// marks the tag as formatted for NDEF and tells whether its data area can be
+/// read and written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CapabilityContainer {
+    /// Version of the mapping, major in the high nibble and minor in the low
+    /// one.
+    pub version: u8,
+    /// Size of the data area, in units of 8 bytes.
+    pub size: u8,
+    /// Read access condition, 4 bits.
+    pub read_access: u8,
+    /// Write access condition, 4 bits.
+    pub write_access: u8,
+}
+
+impl CapabilityContainer {
+    /// Version of the mapping this crate implements, 1.0.
+    pub const VERSION: u8 = 0x10;
+    /// Access condition granting access without any security.
+    pub const ACCESS_GRANTED: u8 = 0x0;
+    /// Access condition of a data area that cannot be written.
+    pub const NO_ACCESS: u8 = 0xF;
+
+    /// Capability container of a data area of `data_area_size` bytes that can
+    /// be read and written. A size that is not a multiple of 8 is rounded
+    /// down, and one larger than [`MAX_DATA_AREA`] is refused with
+    /// [`Error::FieldTooLong`].
+    pub fn new(data_area_size: usize) -> Result<Self> {
+        Ok(CapabilityContainer {
+            version: Self::VERSION,
+            size: u8::try_from(data_area_size / 8).map_err(|_| Error::FieldTooLong)?,
+            read_access: Self::ACCESS_GRANTED,
+            write_access: Self::ACCESS_GRANTED,
+        })
+    }
+
+    /// Reads a capability container, refused with
+    /// [`Error::InvalidCapabilityContainer`] when it does not start with the
+    /// NDEF magic number or has a major version other than 1.
+    pub fn from_bytes(cc: [u8; 4]) -> Result<Self> {
+        if cc[0] != MAGIC || cc[1] >> 4 != Self::VERSION >> 4 {
+            return Err(Error::InvalidCapabilityContainer);
+        }
+        Ok(CapabilityContainer {
+            version: cc[1],
+            size: cc[2],
+            read_access: cc[3] >> 4,
+            write_access: cc[3] & 0x0F,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; 4] {
+        [
+            MAGIC,
+            self.version,
+            self.size,
+            (self.read_access << 4) | (self.write_access & 0x0F),
+        ]
+    }
+
+    /// Bytes of the data area.
+    pub fn data_area_size(&self) -> usize {
+        8 * self.size as usize
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.read_access == Self::ACCESS_GRANTED
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.write_access == Self::ACCESS_GRANTED
+    }
+}
+
+/// TLV of a data area. NULL TLVs, which only pad the data area, and TLVs of
+/// a type this crate does not know are skipped.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tlv {
+    LockControl(ControlTlv),
+    MemoryControl(ControlTlv),
+    /// Serialized NDEF message, empty on a tag that holds none yet
+    Ndef(Buffer),
+    Proprietary(Buffer),
+    /// Last TLV of the data area
+    Terminator,
+}
+
+/// Bytes of the data area set aside by the control TLVs read so far.
+#[derive(Clone, Debug, Default)]
+struct Reserved(heapless::Vec<Range<usize>, MAX_AREAS>);
+
+impl Reserved {
+    /// Sets aside the area at `range` of the tag memory.
+    fn add(&mut self, range: Range<usize>) -> Result<()> {
+        let start = range.start.saturating_sub(DATA_AREA_START);
+        let end = range.end.saturating_sub(DATA_AREA_START);
+        if start < end {
+            self.0.push(start..end).map_err(|_| Error::InvalidTlv)?;
+        }
+        Ok(())
+    }
+
+    /// First byte at or after `pos` that is not set aside.
+    fn skip(&self, mut pos: usize) -> usize {
+        while let Some(range) = self.0.iter().find(|range| range.contains(&pos)) {
+            pos = range.end;
+        }
+        pos
+    }
+
+    /// Bytes from `pos` to `end` that are not set aside.
+    fn free(&self, pos: usize, end: usize) -> usize {
+        (pos..end)
+            .filter(|pos| !self.0.iter().any(|range| range.contains(pos)))
+            .count()
+    }
+}
+
+/// Iterator over the TLVs of a data area, returned by [`tlvs`]. It ends after
+/// the Terminator TLV, at the end of the data area, or after an error.
+#[derive(Clone, Debug)]
+pub struct Tlvs<'a> {
+    data: &'a [u8],
+    pos: usize,
+    reserved: Reserved,
+    done: bool,
+}
+
+/// TLVs of the data area `data`.
+pub fn tlvs(data: &[u8]) -> Tlvs<'_> {
+    Tlvs {
+        data,
+        pos: 0,
+        reserved: Reserved::default(),
+        done: false,
+    }
+}
+
+impl Tlvs<'_> {
+    fn at_end(&mut self) -> bool {
+        self.pos = self.reserved.skip(self.pos);
+        self.pos >= self.data.len()
+    }
+
+    fn read(&mut self) -> Result<u8> {
+        self.pos = self.reserved.skip(self.pos);
+        let byte = *self.data.get(self.pos).ok_or(Error::SliceTooShort)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn read_length(&mut self) -> Result<usize> {
+        match self.read()? {
+            LONG_LENGTH => {
+                let len = u16::from_be_bytes([self.read()?, self.read()?]);
+                // a length that fits in one byte is written in one byte
+                if len < LONG_LENGTH as u16 || len == u16::MAX {
+                    return Err(Error::InvalidTlv);
+                }
+                Ok(len as usize)
+            }
+            len => Ok(len as usize),
+        }
+    }
+
+    fn read_value(&mut self, len: usize) -> Result<Buffer> {
+        let mut value = Buffer::new();
+        for _ in 0..len {
+            write_u8(&mut value, self.read()?)?;
+        }
+        Ok(value)
+    }
+
+    fn read_control(&mut self) -> Result<ControlTlv> {
+        if self.read_length()? != 3 {
+            return Err(Error::InvalidTlv);
+        }
+        Ok(ControlTlv::from_bytes([
+            self.read()?,
+            self.read()?,
+            self.read()?,
+        ]))
+    }
+
+    fn read_tlv(&mut self) -> Result<Option<Tlv>> {
+        loop {
+            if self.at_end() {
+                return Ok(None);
+            }
+            let tlv = match self.read()? {
+                NULL => continue,
+                LOCK_CONTROL => {
+                    let control = self.read_control()?;
+                    self.reserved.add(control.lock_area())?;
+                    Tlv::LockControl(control)
+                }
+                MEMORY_CONTROL => {
+                    let control = self.read_control()?;
+                    self.reserved.add(control.memory_area())?;
+                    Tlv::MemoryControl(control)
+                }
+                NDEF_MESSAGE => {
+                    let len = self.read_length()?;
+                    Tlv::Ndef(self.read_value(len)?)
+                }
+                PROPRIETARY => {
+                    let len = self.read_length()?;
+                    Tlv::Proprietary(self.read_value(len)?)
+                }
+                TERMINATOR => Tlv::Terminator,
+                _ => {
+                    let len = self.read_length()?;
+                    for _ in 0..len {
+                        self.read()?;
+                    }
+                    continue;
+                }
+            };
+            return Ok(Some(tlv));
+        }
+    }
+}
+
+impl Iterator for Tlvs<'_> {
+    type Item = Result<Tlv>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let tlv = self.read_tlv().transpose();
+        self.done = matches!(tlv, None | Some(Err(_)) | Some(Ok(Tlv::Terminator)));
+        tlv
+    }
+}
+
+/// Serialized NDEF message of the first NDEF Message TLV of the data area
+/// `data`, to be parsed with [`Message::try_from`]. It is empty on a tag that
+/// was formatted but holds no message yet.
+pub fn read_ndef(data: &[u8]) -> Result<Buffer> {
+    for tlv in tlvs(data) {
+        if let Tlv::Ndef(ndef) = tlv? {
+            return Ok(ndef);
+        }
+    }
+    Err(Error::MissingNdefTlv)
+}
+
+/// Writes `ndef`, a serialized NDEF message, in an NDEF Message TLV of the data
+/// area `data`, followed by a Terminator TLV if there is room for it. The
+/// Lock Control and Memory Control TLVs at the start of the data area are
+/// kept, and the bytes they set aside are left untouched. The data area is
+/// left untouched as well when the message does not fit.
+pub fn write_ndef(data: &mut [u8], ndef: &[u8]) -> Result<()> {
+    // the control TLVs come before any other
+    let mut controls = tlvs(data);
+    let mut pos = 0;
+    while let Some(Ok(Tlv::LockControl(_) | Tlv::MemoryControl(_))) = controls.next() {
+        pos = controls.pos;
+    }
+    let reserved = controls.reserved;
+
+    let tlv = ndef_header(ndef.len())?;
+    let free = reserved.free(pos, data.len());
+    if tlv.len() + ndef.len() > free {
+        return Err(Error::BufferTooSmall);
+    }
+    let bytes = tlv.iter().chain(ndef);
+    let terminator = (tlv.len() + ndef.len() < free).then_some(&TERMINATOR);
+    for byte in bytes.chain(terminator) {
+        pos = reserved.skip(pos);
+        data[pos] = *byte;
+        pos += 1;
+    }
+    Ok(())
+}
+
+/// Type and length of an NDEF Message TLV holding `len` bytes.
+pub(crate) fn ndef_header(len: usize) -> Result<Buffer> {
+    let mut tlv = Buffer::new();
+    write_u8(&mut tlv, NDEF_MESSAGE)?;
+    match u8::try_from(len) {
+        Ok(len) if len != LONG_LENGTH => write_u8(&mut tlv, len)?,
+        _ => {
+            let len = u16::try_from(len)
+                .ok()
+                .filter(|len| *len != u16::MAX)
+                .ok_or(Error::FieldTooLong)?;
+            write_u8(&mut tlv, LONG_LENGTH)?;
+            crate::write_all(&mut tlv, &len.to_be_bytes())?;
+        }
+    }
+    Ok(tlv)
+}
+
+/// Writes `message` in the data area `data`, see [`write_ndef`].
+pub fn write_message(data: &mut [u8], message: &Message<'_>) -> Result<()> {
+    write_ndef(data, &message.to_vec()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Memory Control TLV sets aside data area bytes 8 and 9, right in the
+    /// NDEF message TLV.
+    const CONTROL: [u8; 5] = [MEMORY_CONTROL, 0x03, 0x60, 0x02, 0x02];
+
+    #[test]
+    fn test_reserved_areas_are_skipped() {
+        let msg = Message::builder().text("en", "hi").build().unwrap();
+        let ndef = msg.to_vec().unwrap();
+
+        let mut data = [0xAA; 24];
+        data[..5].copy_from_slice(&CONTROL);
+        write_message(&mut data, &msg).unwrap();
+        assert_eq!(&data[5..8], &[NDEF_MESSAGE, ndef.len() as u8, ndef[0]]);
+        assert_eq!(&data[8..10], &[0xAA, 0xAA]);
+        assert_eq!(&data[10..10 + ndef.len() - 1], &ndef[1..]);
+        assert_eq!(data[9 + ndef.len()], TERMINATOR);
+
+        let mut tlvs = tlvs(&data);
+        assert_eq!(
+            tlvs.next(),
+            Some(Ok(Tlv::MemoryControl(ControlTlv::from_bytes([
+                0x60, 0x02, 0x02
+            ]))))
+        );
+        assert_eq!(tlvs.next(), Some(Ok(Tlv::Ndef(ndef.clone()))));
+        assert_eq!(tlvs.next(), Some(Ok(Tlv::Terminator)));
+        assert_eq!(tlvs.next(), None);
+        assert_eq!(read_ndef(&data).unwrap(), ndef);
+
+        // the message fills the data area but for the terminator
+        let mut data = [0xAA; 18];
+        data[..5].copy_from_slice(&CONTROL);
+        write_message(&mut data, &msg).unwrap();
+        assert_eq!(read_ndef(&data).unwrap(), ndef);
+        let mut small = [0xAA; 17];
+        small[..5].copy_from_slice(&CONTROL);
+        assert_eq!(write_message(&mut small, &msg), Err(Error::BufferTooSmall));
+        assert_eq!(&small[5..], &[0xAA; 12]);
+    }
+
+    #[test]
+    fn test_tlv_lengths() {
+        // a lock area of 16 bits at byte 160, as on a MIFARE Ultralight C
+        let lock = ControlTlv::from_bytes([0xA0, 0x10, 0x44]);
+        assert_eq!(lock.lock_area(), 160..162);
+        assert_eq!(lock.bytes_locked_per_bit(), 16);
+        assert_eq!(lock.to_bytes(), [0xA0, 0x10, 0x44]);
+
+        let data = [
+            NULL,
+            PROPRIETARY,
+            LONG_LENGTH,
+            0x00,
+            0x01,
+            0x42,
+            NDEF_MESSAGE,
+            0x00,
+        ];
+        let tlvs: Result<heapless::Vec<Tlv, 2>> = tlvs(&data).collect();
+        assert_eq!(
+            tlvs.unwrap_err(),
+            // 1 is written in a single byte
+            Error::InvalidTlv
+        );
+        assert_eq!(read_ndef(&[NDEF_MESSAGE, 0x00]).unwrap(), Buffer::new());
+        assert_eq!(read_ndef(&[TERMINATOR]), Err(Error::MissingNdefTlv));
+        assert_eq!(
+            read_ndef(&[NDEF_MESSAGE, 0x02, 0xD0]),
+            Err(Error::SliceTooShort)
+        );
+        assert_eq!(
+            read_ndef(&[PROPRIETARY, 0x01, 0x42, NDEF_MESSAGE, 0x00]).unwrap(),
+            Buffer::new()
+        );
+
+        let long = [0u8; 300];
+        let mut data = [0u8; 305];
+        write_ndef(&mut data, &long).unwrap();
+        assert_eq!(&data[..4], &[NDEF_MESSAGE, LONG_LENGTH, 0x01, 0x2C]);
+        #[cfg(feature = "alloc")]
+        assert_eq!(read_ndef(&data).unwrap(), &long[..]);
+    }
+}
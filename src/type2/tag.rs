@@ -0,0 +1,436 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use core::ops::Range;
+
+use super::{
+    read_ndef, write_message, write_ndef, CapabilityContainer, ControlTlv, DATA_AREA_START,
+    LOCK_CONTROL, MAX_DATA_AREA, PAGE_LEN,
+};
+use crate::{Buffer, Error, Message, Result};
+
+/// Page of the static lock bytes, after two bytes of the serial number.
+const LOCK_PAGE: usize = 2;
+/// Byte of the first static lock byte.
+const STATIC_LOCK: usize = 10;
+/// Page of the capability container.
+const CC_PAGE: usize = 3;
+/// First byte locked by the dynamic lock bits. The static lock bits lock the
+/// pages before it.
+const DYNAMIC_START: usize = 64;
+/// Bytes a dynamic lock bit locks, as a power of two.
+const BYTES_LOCKED_PER_BIT: u8 = 4;
+/// Cascade tag the first check byte of a 7 byte serial number starts from.
+const CASCADE_TAG: u8 = 0x88;
+
+/// Type 2 Tag of `N` bytes of memory held in memory, which answers the READ and
+/// WRITE commands like a tag does: the serial number cannot be written, the
+/// lock bits and the capability container are one-time programmable, and a
+/// page whose lock bit is set cannot be written anymore.
+///
+/// The data area starts at page 4. A tag larger than 64 bytes puts its
+/// dynamic lock bytes in its last pages, and describes them with a Lock
+/// Control TLV when it is formatted.
+///
+/// ```
+/// use ndef::{type2::VirtualTag, Error, Message};
+///
+/// let msg = Message::builder().text("en", "hi").build().unwrap();
+/// let mut tag = VirtualTag::<168>::new([0x04, 1, 2, 3, 4, 5, 6]);
+/// tag.format().unwrap();
+/// tag.write_message(&msg).unwrap();
+/// tag.make_read_only().unwrap();
+/// assert_eq!(tag.write_message(&msg), Err(Error::ReadOnly));
+///
+/// let ndef = tag.read_ndef().unwrap();
+/// assert_eq!(Message::try_from(&ndef[..]).unwrap(), msg);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VirtualTag<const N: usize> {
+    memory: [u8; N],
+    data_area_size: usize,
+    lock_control: Option<ControlTlv>,
+}
+
+impl<const N: usize> VirtualTag<N> {
+    /// Blank tag with the 7 byte serial number `uid`, not formatted for NDEF.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is not a whole number of pages, or leaves no room for a
+    /// data area of at least 8 bytes.
+    pub fn new(uid: [u8; 7]) -> Self {
+        assert!(
+            N % PAGE_LEN == 0 && N >= DATA_AREA_START + 8,
+            "a Type 2 Tag has at least 6 pages"
+        );
+        let mut memory = [0; N];
+        memory[..3].copy_from_slice(&uid[..3]);
+        memory[3] = CASCADE_TAG ^ uid[0] ^ uid[1] ^ uid[2];
+        memory[4..8].copy_from_slice(&uid[3..]);
+        memory[8] = uid[3] ^ uid[4] ^ uid[5] ^ uid[6];
+        let (data_area_size, lock_control) = layout(N);
+        VirtualTag {
+            memory,
+            data_area_size,
+            lock_control,
+        }
+    }
+
+    /// The whole tag memory.
+    pub fn memory(&self) -> &[u8; N] {
+        &self.memory
+    }
+
+    /// Bytes of the data area the tag is formatted with.
+    pub fn data_area_size(&self) -> usize {
+        self.data_area_size
+    }
+
+    /// Place of the dynamic lock bytes, `None` when the static lock bits lock
+    /// the whole data area.
+    pub fn lock_control(&self) -> Option<ControlTlv> {
+        self.lock_control
+    }
+
+    /// Answers the READ command: the 16 bytes starting at page `page`, rolling
+    /// over to page 0 at the end of the memory.
+    pub fn read_pages(&self, page: usize) -> Result<[u8; 4 * PAGE_LEN]> {
+        let start = page
+            .checked_mul(PAGE_LEN)
+            .filter(|&start| start < N)
+            .ok_or(Error::InvalidAddress)?;
+        let mut data = [0; 4 * PAGE_LEN];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self.memory[(start + i) % N];
+        }
+        Ok(data)
+    }
+
+    /// Answers the WRITE command, reporting [`Error::ReadOnly`] for a locked
+    /// page. The bits of the lock bytes and of the capability container are
+    /// set, never cleared, and the bytes of page 2 that are not lock bytes are
+    /// ignored.
+    pub fn write_page(&mut self, page: usize, data: [u8; PAGE_LEN]) -> Result<()> {
+        let start = page
+            .checked_mul(PAGE_LEN)
+            .filter(|&start| start + PAGE_LEN <= N)
+            .ok_or(Error::InvalidAddress)?;
+        if self.is_locked(page) {
+            return Err(Error::ReadOnly);
+        }
+        let page_range = start..start + PAGE_LEN;
+        if page == LOCK_PAGE {
+            self.set_static_lock_bits([data[2], data[3]]);
+        } else if page == CC_PAGE || overlaps(&self.dynamic_lock_area(), &page_range) {
+            for (byte, bits) in self.memory[page_range].iter_mut().zip(data) {
+                *byte |= bits;
+            }
+        } else {
+            self.memory[page_range].copy_from_slice(&data);
+        }
+        Ok(())
+    }
+
+    /// Capability container of the tag, refused with
+    /// [`Error::InvalidCapabilityContainer`] while the tag is not formatted.
+    pub fn capability_container(&self) -> Result<CapabilityContainer> {
+        let start = CC_PAGE * PAGE_LEN;
+        let mut cc = [0; PAGE_LEN];
+        cc.copy_from_slice(&self.memory[start..start + PAGE_LEN]);
+        CapabilityContainer::from_bytes(cc)
+    }
+
+    /// Formats a blank tag for NDEF: writes its capability container, then a
+    /// data area holding its Lock Control TLV, if any, and an empty NDEF
+    /// message. The capability container is one-time programmable, so a tag
+    /// formatted with another one reports [`Error::ReadOnly`].
+    pub fn format(&mut self) -> Result<()> {
+        let cc = CapabilityContainer::new(self.data_area_size)?;
+        self.write_page(CC_PAGE, cc.to_bytes())?;
+        if self.capability_container()? != cc {
+            return Err(Error::ReadOnly);
+        }
+        let mut memory = self.memory;
+        let data = &mut memory[self.data_area()];
+        data.fill(0);
+        if let Some(control) = self.lock_control {
+            data[..2].copy_from_slice(&[LOCK_CONTROL, 3]);
+            data[2..5].copy_from_slice(&control.to_bytes());
+        }
+        write_ndef(data, &[])?;
+        self.commit(&memory)
+    }
+
+    /// Writes `message` in the data area through WRITE commands. Nothing is
+    /// written when the capability container denies writing or a page the
+    /// message needs is locked, which is reported as [`Error::ReadOnly`].
+    pub fn write_message(&mut self, message: &Message<'_>) -> Result<()> {
+        if !self.capability_container()?.is_writable() {
+            return Err(Error::ReadOnly);
+        }
+        let mut memory = self.memory;
+        write_message(&mut memory[self.data_area()], message)?;
+        self.commit(&memory)
+    }
+
+    /// Serialized NDEF message held by the tag, see [`read_ndef`].
+    pub fn read_ndef(&self) -> Result<Buffer> {
+        let cc = self.capability_container()?;
+        if !cc.is_readable() {
+            return Err(Error::InvalidCapabilityContainer);
+        }
+        let end = (DATA_AREA_START + cc.data_area_size()).min(N);
+        read_ndef(&self.memory[DATA_AREA_START..end])
+    }
+
+    /// Makes a formatted tag read-only for good: the capability container
+    /// denies writing, then every static and dynamic lock bit is set.
+    pub fn make_read_only(&mut self) -> Result<()> {
+        let mut cc = self.capability_container()?;
+        cc.write_access = CapabilityContainer::NO_ACCESS;
+        self.write_page(CC_PAGE, cc.to_bytes())?;
+        self.write_page(LOCK_PAGE, [0, 0, 0xFF, 0xFF])?;
+        if let Some(control) = self.lock_control {
+            let bits = control.size as usize;
+            let mut memory = self.memory;
+            for (i, byte) in memory[control.lock_area()].iter_mut().enumerate() {
+                *byte |= match bits - 8 * i {
+                    remaining @ 0..=7 => (1 << remaining) - 1,
+                    _ => 0xFF,
+                };
+            }
+            for page in pages(&control.lock_area()) {
+                self.write_page(page, page_of(&memory, page))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn data_area(&self) -> Range<usize> {
+        DATA_AREA_START..DATA_AREA_START + self.data_area_size
+    }
+
+    fn dynamic_lock_area(&self) -> Range<usize> {
+        self.lock_control
+            .map_or(0..0, |control| control.lock_area())
+    }
+
+    fn lock_bit(&self, byte: usize, bit: usize) -> bool {
+        self.memory[byte + bit / 8] & (1 << (bit % 8)) != 0
+    }
+
+    /// Whether a WRITE command to `page` is refused.
+    fn is_locked(&self, page: usize) -> bool {
+        match page {
+            0 | 1 => true,
+            LOCK_PAGE => false,
+            3..=15 => self.lock_bit(STATIC_LOCK, page),
+            _ => {
+                let Some(control) = self.lock_control else {
+                    return false;
+                };
+                let bit =
+                    (page * PAGE_LEN).saturating_sub(DYNAMIC_START) >> control.bytes_locked_per_bit;
+                page * PAGE_LEN < DATA_AREA_START + self.data_area_size
+                    && bit < control.size as usize
+                    && self.lock_bit(control.byte_address(), bit)
+            }
+        }
+    }
+
+    /// Sets the static lock bits, except those frozen by a block-locking bit:
+    /// bit 0 freezes the lock bit of the capability container, bit 1 those of
+    /// pages 4 to 9 and bit 2 those of pages 10 to 15.
+    fn set_static_lock_bits(&mut self, bits: [u8; 2]) {
+        let block = self.memory[STATIC_LOCK];
+        let mut mask = [0xFF, 0xFF];
+        if block & 0x01 != 0 {
+            mask[0] &= !0x08;
+        }
+        if block & 0x02 != 0 {
+            mask[0] &= !0xF0;
+            mask[1] &= !0x03;
+        }
+        if block & 0x04 != 0 {
+            mask[1] &= !0xFC;
+        }
+        self.memory[STATIC_LOCK] |= bits[0] & mask[0];
+        self.memory[STATIC_LOCK + 1] |= bits[1] & mask[1];
+    }
+
+    /// Writes the pages of the data area that differ in `memory`, after
+    /// checking that none of them is locked.
+    fn commit(&mut self, memory: &[u8; N]) -> Result<()> {
+        let changed = |tag: &Self, page: usize| page_of(memory, page) != page_of(&tag.memory, page);
+        if pages(&self.data_area()).any(|page| changed(self, page) && self.is_locked(page)) {
+            return Err(Error::ReadOnly);
+        }
+        for page in pages(&self.data_area()) {
+            if changed(self, page) {
+                self.write_page(page, page_of(memory, page))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size of the data area of a tag of `len` bytes, and place of its dynamic
+/// lock bytes. They take the last pages of the memory, at an address a Lock
+/// Control TLV can give with a 4 bit page number.
+fn layout(len: usize) -> (usize, Option<ControlTlv>) {
+    let fixed = |end: usize| ((end - DATA_AREA_START) / 8 * 8).min(MAX_DATA_AREA);
+    if len <= DYNAMIC_START {
+        return (fixed(len), None);
+    }
+    let bits = (len - DYNAMIC_START).div_ceil(1 << BYTES_LOCKED_PER_BIT);
+    let mut start = len - bits.div_ceil(8).next_multiple_of(PAGE_LEN);
+    let mut bytes_per_page = 2;
+    while start >> bytes_per_page > 0x0F {
+        bytes_per_page += 1;
+    }
+    if start % (1 << bytes_per_page) > 0x0F {
+        start = start >> bytes_per_page << bytes_per_page;
+    }
+    let data_area_size = fixed(start);
+    let bits = data_area_size
+        .saturating_sub(DYNAMIC_START - DATA_AREA_START)
+        .div_ceil(1 << BYTES_LOCKED_PER_BIT);
+    if bits == 0 {
+        return (data_area_size, None);
+    }
+    let control = ControlTlv {
+        page_address: (start >> bytes_per_page) as u8,
+        byte_offset: (start % (1 << bytes_per_page)) as u8,
+        size: bits as u8,
+        bytes_locked_per_bit: BYTES_LOCKED_PER_BIT,
+        bytes_per_page,
+    };
+    (data_area_size, Some(control))
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// Pages holding a byte of `range`.
+fn pages(range: &Range<usize>) -> Range<usize> {
+    range.start / PAGE_LEN..range.end.div_ceil(PAGE_LEN)
+}
+
+fn page_of(memory: &[u8], page: usize) -> [u8; PAGE_LEN] {
+    let mut data = [0; PAGE_LEN];
+    data.copy_from_slice(&memory[page * PAGE_LEN..(page + 1) * PAGE_LEN]);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UID: [u8; 7] = [0x04, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+
+    #[test]
+    fn test_layout() {
+        let tag = VirtualTag::<64>::new(UID);
+        assert_eq!(tag.data_area_size(), 48);
+        assert_eq!(tag.lock_control(), None);
+        assert_eq!(tag.memory()[3], 0x88 ^ 0x04 ^ 0x01 ^ 0x02);
+        assert_eq!(tag.memory()[8], 0x03 ^ 0x04 ^ 0x05 ^ 0x06);
+
+        // the layout of an NTAG213
+        let tag = VirtualTag::<168>::new(UID);
+        assert_eq!(tag.data_area_size(), 144);
+        let control = tag.lock_control().unwrap();
+        assert_eq!(control.to_bytes(), [0xA4, 0x06, 0x44]);
+        assert_eq!(control.lock_area(), 164..165);
+
+        // a lock area at byte 340 cannot be given by a control TLV
+        let (data_area_size, control) = layout(344);
+        assert_eq!(data_area_size, 304);
+        assert_eq!(control.unwrap().lock_area(), 320..322);
+    }
+
+    #[test]
+    fn test_provisioning() {
+        let msg = Message::builder()
+            .uri("https://example.com")
+            .build()
+            .unwrap();
+        let mut tag = VirtualTag::<168>::new(UID);
+        assert_eq!(tag.read_ndef(), Err(Error::InvalidCapabilityContainer));
+        tag.format().unwrap();
+        assert_eq!(&tag.memory()[12..16], &[0xE1, 0x10, 0x12, 0x00]);
+        assert_eq!(
+            &tag.memory()[16..24],
+            &[LOCK_CONTROL, 3, 0xA4, 0x06, 0x44, 0x03, 0x00, 0xFE]
+        );
+        assert_eq!(tag.read_ndef().unwrap(), Buffer::new());
+
+        tag.write_message(&msg).unwrap();
+        assert_eq!(tag.read_ndef().unwrap(), msg.to_vec().unwrap());
+        let written = tag.clone();
+        assert_eq!(tag.format(), Ok(()));
+        assert_eq!(tag.read_ndef().unwrap(), Buffer::new());
+
+        tag.make_read_only().unwrap();
+        assert_eq!(tag.memory()[10..12], [0xFF, 0xFF]);
+        assert_eq!(tag.memory()[164], 0x3F);
+        assert_eq!(tag.write_message(&msg), Err(Error::ReadOnly));
+        assert_eq!(tag.write_page(4, [0; 4]), Err(Error::ReadOnly));
+        assert_eq!(tag.write_page(30, [0; 4]), Err(Error::ReadOnly));
+        assert_eq!(tag.capability_container().unwrap().write_access, 0x0F);
+
+        // locked pages are refused before anything is written
+        let mut tag = written;
+        tag.write_page(LOCK_PAGE, [0, 0, 0, 0x80]).unwrap();
+        let long = Message::builder()
+            .external("example.com", "t", &[0xAA; 60])
+            .build()
+            .unwrap();
+        let before = tag.clone();
+        assert_eq!(tag.write_message(&long), Err(Error::ReadOnly));
+        assert_eq!(tag, before);
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut tag = VirtualTag::<64>::new(UID);
+        assert_eq!(tag.write_page(0, [0; 4]), Err(Error::ReadOnly));
+        assert_eq!(tag.write_page(16, [0; 4]), Err(Error::InvalidAddress));
+        assert_eq!(tag.read_pages(16), Err(Error::InvalidAddress));
+        assert_eq!(
+            tag.write_page(usize::MAX, [0; 4]),
+            Err(Error::InvalidAddress)
+        );
+        assert_eq!(tag.read_pages(usize::MAX), Err(Error::InvalidAddress));
+        assert_eq!(&tag.read_pages(15).unwrap()[4..], &tag.memory()[..12]);
+
+        // a block-locking bit freezes the lock bits of pages 10 to 15
+        tag.write_page(LOCK_PAGE, [0xFF, 0xFF, 0x04, 0x01]).unwrap();
+        tag.write_page(LOCK_PAGE, [0, 0, 0, 0xFF]).unwrap();
+        assert_eq!(
+            tag.memory()[8..12],
+            [0x03 ^ 0x04 ^ 0x05 ^ 0x06, 0, 0x04, 0x03]
+        );
+        assert_eq!(tag.write_page(8, [1; 4]), Err(Error::ReadOnly));
+        tag.write_page(10, [1; 4]).unwrap();
+
+        tag.write_page(CC_PAGE, [0xE1, 0x10, 0x06, 0x00]).unwrap();
+        tag.write_page(CC_PAGE, [0x00, 0x00, 0x00, 0x0F]).unwrap();
+        assert_eq!(
+            tag.capability_container().unwrap(),
+            CapabilityContainer {
+                version: 0x10,
+                size: 6,
+                read_access: 0,
+                write_access: 0x0F,
+            }
+        );
+        assert_eq!(
+            CapabilityContainer::from_bytes([0xE1, 0x20, 0x06, 0x00]),
+            Err(Error::InvalidCapabilityContainer)
+        );
+    }
+}
@@ -0,0 +1,300 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! NDEF blocks of an NFC Forum Type 3 Tag, such as a FeliCa Lite-S, which are
+//! read and written 16 bytes at a time: an Attribute Information Block
+//! describing the tag comes first, then the blocks of the NDEF message.
+//!
+//! The functions of this module take the NDEF blocks back to back, starting
+//! with the Attribute Information Block, as they are read with Check commands.
+//!
+//! ```
+//! use ndef::{type3, Message};
+//!
+//! let msg = Message::builder().text("en", "hi").build().unwrap();
+//! let ndef = msg.to_vec().unwrap();
+//! let info = type3::AttributeInformation::new(4, 1, 13);
+//!
+//! let mut memory = [0; 14 * type3::BLOCK_LEN];
+//! for (number, block) in type3::blocks(&info, &ndef).unwrap() {
+//!     let start = number * type3::BLOCK_LEN;
+//!     memory[start..start + type3::BLOCK_LEN].copy_from_slice(&block);
+//! }
+//!
+//! let ndef = type3::read_ndef(&memory).unwrap();
+//! assert_eq!(Message::try_from(ndef).unwrap(), msg);
+//! ```
+
+use core::slice::Chunks;
+
+use crate::{Error, Result};
+
+/// Bytes of a block, the unit a Type 3 Tag is read and written in.
+pub const BLOCK_LEN: usize = 16;
+
+const WRITE_FLAG_OFF: u8 = 0x00;
+const WRITE_FLAG_ON: u8 = 0x0F;
+const READ_ONLY: u8 = 0x00;
+const READ_WRITE: u8 = 0x01;
+
+/// Attribute Information Block of a Type 3 Tag, the first of its NDEF blocks,
+/// which tells how many blocks the tag takes per command and how long the
+/// NDEF message is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AttributeInformation {
+    /// Version of the mapping, major in the high nibble and minor in the low
+    /// one.
+    pub version: u8,
+    /// Most blocks a single Check command reads.
+    pub nbr: u8,
+    /// Most blocks a single Update command writes.
+    pub nbw: u8,
+    /// Blocks available to the NDEF message, after this one.
+    pub nmaxb: u16,
+    /// Whether a write of the NDEF message was started and not finished.
+    pub writing: bool,
+    /// Whether the NDEF message may be written.
+    pub writable: bool,
+    /// Bytes of the NDEF message, 24 bits.
+    pub ln: u32,
+}
+
+impl AttributeInformation {
+    /// Version of the mapping this crate implements, 1.0.
+    pub const VERSION: u8 = 0x10;
+
+    /// Attribute information of a writable tag that holds no message.
+    pub fn new(nbr: u8, nbw: u8, nmaxb: u16) -> Self {
+        AttributeInformation {
+            version: Self::VERSION,
+            nbr,
+            nbw,
+            nmaxb,
+            writing: false,
+            writable: true,
+            ln: 0,
+        }
+    }
+
+    /// Reads an Attribute Information Block, refused with
+    /// [`Error::InvalidChecksum`] when its checksum does not match its bytes,
+    /// and with [`Error::InvalidCapabilityContainer`] when it has a major
+    /// version other than 1 or announces a message longer than its blocks.
+    pub fn from_bytes(block: [u8; BLOCK_LEN]) -> Result<Self> {
+        if checksum(&block[..14]).to_be_bytes() != block[14..] {
+            return Err(Error::InvalidChecksum);
+        }
+        let info = AttributeInformation {
+            version: block[0],
+            nbr: block[1],
+            nbw: block[2],
+            nmaxb: u16::from_be_bytes([block[3], block[4]]),
+            writing: block[9] != WRITE_FLAG_OFF,
+            writable: block[10] == READ_WRITE,
+            ln: u32::from_be_bytes([0, block[11], block[12], block[13]]),
+        };
+        if info.version >> 4 != Self::VERSION >> 4 || info.ln as usize > info.ndef_capacity() {
+            return Err(Error::InvalidCapabilityContainer);
+        }
+        Ok(info)
+    }
+
+    /// Attribute Information Block of `self`, with its checksum.
+    pub fn to_bytes(&self) -> [u8; BLOCK_LEN] {
+        let nmaxb = self.nmaxb.to_be_bytes();
+        let ln = self.ln.to_be_bytes();
+        let mut block = [
+            self.version,
+            self.nbr,
+            self.nbw,
+            nmaxb[0],
+            nmaxb[1],
+            0,
+            0,
+            0,
+            0,
+            if self.writing {
+                WRITE_FLAG_ON
+            } else {
+                WRITE_FLAG_OFF
+            },
+            if self.writable { READ_WRITE } else { READ_ONLY },
+            ln[1],
+            ln[2],
+            ln[3],
+            0,
+            0,
+        ];
+        let checksum = checksum(&block[..14]).to_be_bytes();
+        block[14..].copy_from_slice(&checksum);
+        block
+    }
+
+    /// Most bytes of an NDEF message.
+    pub fn ndef_capacity(&self) -> usize {
+        BLOCK_LEN * self.nmaxb as usize
+    }
+}
+
+/// Sum of the bytes before the checksum of an Attribute Information Block.
+fn checksum(bytes: &[u8]) -> u16 {
+    bytes.iter().map(|byte| u16::from(*byte)).sum()
+}
+
+/// Serialized NDEF message of `memory`, the NDEF blocks of a tag starting with
+/// its Attribute Information Block, to be parsed with
+/// [`Message::try_from`](crate::Message::try_from). A message whose write was
+/// not finished is refused with [`Error::WriteInProgress`].
+pub fn read_ndef(memory: &[u8]) -> Result<&[u8]> {
+    if memory.len() < BLOCK_LEN {
+        return Err(Error::SliceTooShort);
+    }
+    let (info, data) = memory.split_at(BLOCK_LEN);
+    let mut block = [0; BLOCK_LEN];
+    block.copy_from_slice(info);
+    let info = AttributeInformation::from_bytes(block)?;
+    if info.writing {
+        return Err(Error::WriteInProgress);
+    }
+    data.get(..info.ln as usize).ok_or(Error::SliceTooShort)
+}
+
+/// Blocks writing an NDEF message, each with its number among the NDEF blocks,
+/// see [`blocks`].
+#[derive(Clone, Debug)]
+pub struct Blocks<'a> {
+    info: AttributeInformation,
+    data: Chunks<'a, u8>,
+    number: usize,
+    done: bool,
+}
+
+/// Blocks writing `ndef`, a serialized NDEF message, on a tag described by
+/// `info`, in the order that keeps a reader from taking a partly written
+/// message: the Attribute Information Block marking the write as started, the
+/// blocks of the message, padded with zeros, then the Attribute Information
+/// Block with the length of the message. A message longer than the tag holds
+/// is refused with [`Error::BufferTooSmall`], and a tag that is not writable
+/// with [`Error::ReadOnly`].
+pub fn blocks<'a>(info: &AttributeInformation, ndef: &'a [u8]) -> Result<Blocks<'a>> {
+    if !info.writable {
+        return Err(Error::ReadOnly);
+    }
+    if ndef.len() > info.ndef_capacity() {
+        return Err(Error::BufferTooSmall);
+    }
+    let info = AttributeInformation {
+        writing: true,
+        ln: ndef.len() as u32,
+        ..*info
+    };
+    Ok(Blocks {
+        info,
+        data: ndef.chunks(BLOCK_LEN),
+        number: 0,
+        done: false,
+    })
+}
+
+impl Iterator for Blocks<'_> {
+    type Item = (usize, [u8; BLOCK_LEN]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.number == 0 {
+            self.number = 1;
+            return Some((0, self.info.to_bytes()));
+        }
+        if let Some(chunk) = self.data.next() {
+            let mut block = [0; BLOCK_LEN];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.number += 1;
+            return Some((self.number - 1, block));
+        }
+        self.done = true;
+        self.info.writing = false;
+        Some((0, self.info.to_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Message;
+
+    /// Attribute Information Block of a FeliCa Lite-S holding a 21-byte message.
+    const INFO: [u8; BLOCK_LEN] = [
+        0x10, 0x04, 0x01, 0x00, 0x0D, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x15, 0x00,
+        0x38,
+    ];
+
+    #[test]
+    fn test_attribute_information() {
+        let info = AttributeInformation::from_bytes(INFO).unwrap();
+        assert_eq!(
+            info,
+            AttributeInformation {
+                ln: 0x15,
+                ..AttributeInformation::new(4, 1, 13)
+            }
+        );
+        assert_eq!(info.to_bytes(), INFO);
+        assert_eq!(info.ndef_capacity(), 208);
+
+        let mut block = INFO;
+        block[13] = 0x16;
+        assert_eq!(
+            AttributeInformation::from_bytes(block),
+            Err(Error::InvalidChecksum)
+        );
+        block[15] = 0x39;
+        assert!(AttributeInformation::from_bytes(block).is_ok());
+        block[0] = 0x20;
+        block[15] = 0x49;
+        assert_eq!(
+            AttributeInformation::from_bytes(block),
+            Err(Error::InvalidCapabilityContainer)
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let msg = Message::builder()
+            .uri("https://foundation.xyz/passport")
+            .build()
+            .unwrap();
+        let ndef = msg.to_vec().unwrap();
+        let info = AttributeInformation::new(4, 1, 3);
+        let mut memory = [0xAA; 4 * BLOCK_LEN];
+
+        let mut writes = blocks(&info, &ndef).unwrap();
+        let (number, first) = writes.next().unwrap();
+        assert_eq!(number, 0);
+        memory[..BLOCK_LEN].copy_from_slice(&first);
+        assert_eq!(read_ndef(&memory), Err(Error::WriteInProgress));
+
+        let mut numbers = heapless::Vec::<usize, 4>::new();
+        for (number, block) in writes {
+            let start = number * BLOCK_LEN;
+            memory[start..start + BLOCK_LEN].copy_from_slice(&block);
+            numbers.push(number).unwrap();
+        }
+        assert_eq!(&numbers[..], &[1, 2, 0]);
+        assert_eq!(read_ndef(&memory).unwrap(), &ndef[..]);
+        assert_eq!(memory[BLOCK_LEN + ndef.len()], 0);
+        assert_eq!(Message::try_from(read_ndef(&memory).unwrap()).unwrap(), msg);
+
+        let full = [0; 3 * BLOCK_LEN + 1];
+        assert!(matches!(blocks(&info, &full), Err(Error::BufferTooSmall)));
+        let read_only = AttributeInformation {
+            writable: false,
+            ..info
+        };
+        assert!(matches!(blocks(&read_only, &ndef), Err(Error::ReadOnly)));
+        assert_eq!(read_ndef(&memory[..BLOCK_LEN]), Err(Error::SliceTooShort));
+        assert_eq!(read_ndef(&memory[..4]), Err(Error::SliceTooShort));
+    }
+}
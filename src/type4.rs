@@ -0,0 +1,349 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! NDEF Tag Application of an NFC Forum Type 4 Tag, which an ISO-DEP card
+//! serves through ISO 7816-4 APDUs: a capability container file tells where
+//! the NDEF file is, and the NDEF file holds the length of the message and the
+//! message itself.
+//!
+//! [`Emulator`] answers those APDUs for a device emulating a tag, and
+//! [`Reader`] sends them to read or write the message of a tag.
+
+use crate::{Error, Result};
+
+mod emulator;
+mod reader;
+
+pub use emulator::{Emulator, Response};
+pub use reader::{MockTransceiver, Reader, Transceive};
+
+/// Application identifier of the NDEF Tag Application.
+pub const NDEF_AID: [u8; 7] = [0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01];
+/// File identifier of the capability container file.
+pub const CC_FILE_ID: u16 = 0xE103;
+/// File identifier the NDEF file is given by this crate.
+pub const NDEF_FILE_ID: u16 = 0xE104;
+
+/// Access condition granting access without any security.
+pub const ACCESS_GRANTED: u8 = 0x00;
+/// Access condition of a file that cannot be written.
+pub const NO_ACCESS: u8 = 0xFF;
+
+const CLA: u8 = 0x00;
+const INS_SELECT: u8 = 0xA4;
+const INS_READ_BINARY: u8 = 0xB0;
+const INS_READ_BINARY_ODO: u8 = 0xB1;
+const INS_UPDATE_BINARY: u8 = 0xD6;
+const INS_UPDATE_BINARY_ODO: u8 = 0xD7;
+
+/// SELECT parameters choosing an application by name.
+const SELECT_BY_NAME: [u8; 2] = [0x04, 0x00];
+/// SELECT parameters choosing a file by identifier, without response data.
+const SELECT_BY_ID: [u8; 2] = [0x00, 0x0C];
+
+/// Tag of the offset data object of READ and UPDATE BINARY with an ODO.
+const ODO_TAG: u8 = 0x54;
+/// Tag of the discretionary data object carrying the file content.
+const DDO_TAG: u8 = 0x53;
+
+const NDEF_FILE_CONTROL: u8 = 0x04;
+const EXTENDED_NDEF_FILE_CONTROL: u8 = 0x06;
+
+/// Version of the NDEF mapping. Version 3.0 announces the NDEF file with a
+/// 4 byte size, gives the message length in 4 bytes (ENLEN) instead of 2
+/// (NLEN), and reaches offsets past 32767 with READ and UPDATE BINARY commands
+/// carrying an offset data object.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MappingVersion {
+    #[default]
+    V2,
+    V3,
+}
+
+impl MappingVersion {
+    /// Version byte of the capability container.
+    pub fn code(&self) -> u8 {
+        match self {
+            MappingVersion::V2 => 0x20,
+            MappingVersion::V3 => 0x30,
+        }
+    }
+
+    /// Bytes of the message length at the start of the NDEF file.
+    pub fn length_len(&self) -> usize {
+        match self {
+            MappingVersion::V2 => 2,
+            MappingVersion::V3 => 4,
+        }
+    }
+
+    /// Largest NDEF file the version can announce and address.
+    pub fn max_file_size(&self) -> usize {
+        match self {
+            // READ BINARY without an offset data object has a 15 bit offset
+            MappingVersion::V2 => 0x7FFF,
+            MappingVersion::V3 => 0xFFFF_FFFE,
+        }
+    }
+}
+
+/// Status word ending every response APDU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StatusWord(pub u16);
+
+impl StatusWord {
+    pub const OK: StatusWord = StatusWord(0x9000);
+    pub const WRONG_LENGTH: StatusWord = StatusWord(0x6700);
+    pub const SECURITY_NOT_SATISFIED: StatusWord = StatusWord(0x6982);
+    /// No file is selected.
+    pub const NOT_ALLOWED: StatusWord = StatusWord(0x6986);
+    pub const WRONG_DATA: StatusWord = StatusWord(0x6A80);
+    pub const NOT_FOUND: StatusWord = StatusWord(0x6A82);
+    pub const INCORRECT_P1_P2: StatusWord = StatusWord(0x6A86);
+    /// The offset is outside the file.
+    pub const WRONG_PARAMETERS: StatusWord = StatusWord(0x6B00);
+    pub const INS_NOT_SUPPORTED: StatusWord = StatusWord(0x6D00);
+    pub const CLA_NOT_SUPPORTED: StatusWord = StatusWord(0x6E00);
+
+    pub fn to_bytes(self) -> [u8; 2] {
+        self.0.to_be_bytes()
+    }
+}
+
+/// Content of the capability container file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CapabilityContainer {
+    pub version: MappingVersion,
+    /// Most bytes a READ BINARY response may carry.
+    pub mle: u16,
+    /// Most bytes an UPDATE BINARY command may carry.
+    pub mlc: u16,
+    pub file_id: u16,
+    /// Size of the NDEF file, its message length included.
+    pub max_ndef_size: u32,
+    pub read_access: u8,
+    pub write_access: u8,
+}
+
+/// Longest capability container file, that of version 3.0.
+pub const MAX_CC_LEN: usize = 17;
+
+impl CapabilityContainer {
+    /// Reads a capability container file, refused with
+    /// [`Error::InvalidCapabilityContainer`] when its version is not 2 or 3
+    /// or it does not describe an NDEF file.
+    pub fn from_bytes(cc: &[u8]) -> Result<Self> {
+        let invalid = Error::InvalidCapabilityContainer;
+        let &[len0, len1, version, mle0, mle1, mlc0, mlc1, tag, tlv_len, ref value @ ..] = cc
+        else {
+            return Err(Error::SliceTooShort);
+        };
+        if usize::from(u16::from_be_bytes([len0, len1])) > cc.len() {
+            return Err(Error::SliceTooShort);
+        }
+        let version = match version >> 4 {
+            2 => MappingVersion::V2,
+            3 => MappingVersion::V3,
+            _ => return Err(invalid),
+        };
+        let (file_id, max_ndef_size, access) = match (tag, tlv_len, value) {
+            (NDEF_FILE_CONTROL, 6, &[id0, id1, size0, size1, ref access @ ..]) => (
+                [id0, id1],
+                u32::from(u16::from_be_bytes([size0, size1])),
+                access,
+            ),
+            (EXTENDED_NDEF_FILE_CONTROL, 8, &[id0, id1, s0, s1, s2, s3, ref access @ ..]) => {
+                ([id0, id1], u32::from_be_bytes([s0, s1, s2, s3]), access)
+            }
+            _ => return Err(invalid),
+        };
+        let &[read_access, write_access, ..] = access else {
+            return Err(Error::SliceTooShort);
+        };
+        Ok(CapabilityContainer {
+            version,
+            mle: u16::from_be_bytes([mle0, mle1]),
+            mlc: u16::from_be_bytes([mlc0, mlc1]),
+            file_id: u16::from_be_bytes(file_id),
+            max_ndef_size,
+            read_access,
+            write_access,
+        })
+    }
+
+    /// Content of the capability container file. Version 2.0 describes the
+    /// NDEF file with an NDEF File Control TLV, version 3.0 with an Extended
+    /// one.
+    pub fn to_bytes(&self) -> heapless::Vec<u8, MAX_CC_LEN> {
+        let mut cc = heapless::Vec::new();
+        let (len, control) = match self.version {
+            MappingVersion::V2 => (15u16, [NDEF_FILE_CONTROL, 6]),
+            MappingVersion::V3 => (17, [EXTENDED_NDEF_FILE_CONTROL, 8]),
+        };
+        let mut push = |bytes: &[u8]| cc.extend_from_slice(bytes).unwrap();
+        push(&len.to_be_bytes());
+        push(&[self.version.code()]);
+        push(&self.mle.to_be_bytes());
+        push(&self.mlc.to_be_bytes());
+        push(&control);
+        push(&self.file_id.to_be_bytes());
+        match self.version {
+            MappingVersion::V2 => push(&(self.max_ndef_size as u16).to_be_bytes()),
+            MappingVersion::V3 => push(&self.max_ndef_size.to_be_bytes()),
+        }
+        push(&[self.read_access, self.write_access]);
+        cc
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.read_access == ACCESS_GRANTED
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.write_access == ACCESS_GRANTED
+    }
+}
+
+/// Command APDU, in the short or extended length format.
+#[derive(Debug)]
+struct Command<'a> {
+    cla: u8,
+    ins: u8,
+    p1: u8,
+    p2: u8,
+    data: &'a [u8],
+    /// Bytes expected in the response, `None` without an Le field.
+    le: Option<usize>,
+}
+
+impl<'a> Command<'a> {
+    fn parse(apdu: &'a [u8]) -> Option<Self> {
+        let [cla, ins, p1, p2, ref body @ ..] = *apdu else {
+            return None;
+        };
+        let short_le = |le: u8| Some(if le == 0 { 256 } else { usize::from(le) });
+        let extended_le = |le: [u8; 2]| match u16::from_be_bytes(le) {
+            0 => Some(65536),
+            le => Some(usize::from(le)),
+        };
+        let (data, le) = match *body {
+            [] => (&[][..], None),
+            [le] => (&[][..], short_le(le)),
+            [0, le0, le1] => (&[][..], extended_le([le0, le1])),
+            [0, lc0, lc1, ref rest @ ..] if lc0 != 0 || lc1 != 0 => {
+                let lc = usize::from(u16::from_be_bytes([lc0, lc1]));
+                match rest.len().checked_sub(lc)? {
+                    0 => (rest, None),
+                    2 => (&rest[..lc], extended_le([rest[lc], rest[lc + 1]])),
+                    _ => return None,
+                }
+            }
+            [lc, ref rest @ ..] => {
+                let lc = usize::from(lc);
+                match rest.len().checked_sub(lc)? {
+                    0 => (rest, None),
+                    1 => (&rest[..lc], short_le(rest[lc])),
+                    _ => return None,
+                }
+            }
+        };
+        Some(Command {
+            cla,
+            ins,
+            p1,
+            p2,
+            data,
+            le,
+        })
+    }
+}
+
+/// Reads a BER-TLV length at the start of `data`, returning it with the bytes
+/// that follow it.
+fn read_ber_length(data: &[u8]) -> Option<(usize, &[u8])> {
+    match *data {
+        [len @ 0..=0x7F, ref rest @ ..] => Some((usize::from(len), rest)),
+        [0x81, len, ref rest @ ..] => Some((usize::from(len), rest)),
+        [0x82, len0, len1, ref rest @ ..] => {
+            Some((usize::from(u16::from_be_bytes([len0, len1])), rest))
+        }
+        _ => None,
+    }
+}
+
+/// Bytes of the BER-TLV length of a value of `len` bytes.
+fn ber_length_len(len: usize) -> usize {
+    match len {
+        0..=0x7F => 1,
+        0x80..=0xFF => 2,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_container() {
+        let cc = CapabilityContainer {
+            version: MappingVersion::V2,
+            mle: 0xFF,
+            mlc: 0xFF,
+            file_id: NDEF_FILE_ID,
+            max_ndef_size: 0x0800,
+            read_access: ACCESS_GRANTED,
+            write_access: NO_ACCESS,
+        };
+        let bytes = cc.to_bytes();
+        assert_eq!(
+            &bytes[..],
+            &[
+                0x00, 0x0F, 0x20, 0x00, 0xFF, 0x00, 0xFF, 0x04, 0x06, 0xE1, 0x04, 0x08, 0x00, 0x00,
+                0xFF
+            ]
+        );
+        assert_eq!(CapabilityContainer::from_bytes(&bytes), Ok(cc));
+
+        let cc = CapabilityContainer {
+            version: MappingVersion::V3,
+            max_ndef_size: 0x0001_0000,
+            ..cc
+        };
+        let bytes = cc.to_bytes();
+        assert_eq!(bytes.len(), MAX_CC_LEN);
+        assert_eq!(&bytes[7..13], &[0x06, 0x08, 0xE1, 0x04, 0x00, 0x01]);
+        assert_eq!(CapabilityContainer::from_bytes(&bytes), Ok(cc));
+
+        let mut bytes = bytes;
+        bytes[2] = 0x10;
+        assert_eq!(
+            CapabilityContainer::from_bytes(&bytes),
+            Err(Error::InvalidCapabilityContainer)
+        );
+        assert_eq!(
+            CapabilityContainer::from_bytes(&bytes[..12]),
+            Err(Error::SliceTooShort)
+        );
+    }
+
+    #[test]
+    fn test_command_formats() {
+        let command = Command::parse(&[0x00, 0xB0, 0x00, 0x02, 0x00]).unwrap();
+        assert_eq!((command.data, command.le), (&[][..], Some(256)));
+        let command = Command::parse(&[0x00, 0xD6, 0x00, 0x00, 0x02, 0x00, 0x05]).unwrap();
+        assert_eq!((command.data, command.le), (&[0x00, 0x05][..], None));
+        let command = Command::parse(&[0x00, 0xA4, 0x04, 0x00, 0x01, 0xD2, 0x00]).unwrap();
+        assert_eq!((command.data, command.le), (&[0xD2][..], Some(256)));
+        let command = Command::parse(&[0x00, 0xB0, 0x00, 0x00, 0x00, 0x01, 0x00]).unwrap();
+        assert_eq!((command.data, command.le), (&[][..], Some(256)));
+        let command =
+            Command::parse(&[0x00, 0xD6, 0x00, 0x00, 0x00, 0x00, 0x01, 0x42, 0x00, 0x00]).unwrap();
+        assert_eq!((command.data, command.le), (&[0x42][..], Some(65536)));
+        assert!(Command::parse(&[0x00, 0xB0, 0x00]).is_none());
+        assert!(Command::parse(&[0x00, 0xD6, 0x00, 0x00, 0x03, 0x01]).is_none());
+    }
+}
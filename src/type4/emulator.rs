@@ -0,0 +1,468 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::{
+    ber_length_len, read_ber_length, CapabilityContainer, Command, MappingVersion, StatusWord,
+    ACCESS_GRANTED, CC_FILE_ID, CLA, DDO_TAG, INS_READ_BINARY, INS_READ_BINARY_ODO, INS_SELECT,
+    INS_UPDATE_BINARY, INS_UPDATE_BINARY_ODO, NDEF_AID, NDEF_FILE_ID, NO_ACCESS, ODO_TAG,
+    SELECT_BY_ID, SELECT_BY_NAME,
+};
+use crate::{Error, Message, Result};
+
+/// Most bytes the emulator puts in a READ BINARY response, and accepts in an
+/// UPDATE BINARY command, so that every APDU fits the short format.
+const MAX_LE: usize = 0xFF;
+
+/// Response APDU: the data, if any, then the status word.
+pub type Response = heapless::Vec<u8, { MAX_LE + 2 }>;
+
+/// What the last SELECT chose.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Selected {
+    Nothing,
+    Application,
+    CcFile,
+    NdefFile,
+}
+
+/// NDEF Tag Application of a Type 4 Tag emulated by the device, with an NDEF
+/// file of `N` bytes. It answers the command APDUs a reader sends through
+/// [`Emulator::process`], serving the message set with
+/// [`Emulator::set_message`] and keeping the one the reader writes, unless it
+/// is made read-only.
+///
+/// ```
+/// use ndef::{type4::Emulator, Message};
+///
+/// let msg = Message::builder().uri("https://example.com").build().unwrap();
+/// let mut emulator = Emulator::<256>::default();
+/// emulator.set_message(&msg).unwrap();
+///
+/// let select = [0x00, 0xA4, 0x04, 0x00, 0x07, 0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01, 0x00];
+/// assert_eq!(&emulator.process(&select)[..], &[0x90, 0x00]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Emulator<const N: usize> {
+    version: MappingVersion,
+    selected: Selected,
+    file: [u8; N],
+    read_only: bool,
+    written: bool,
+}
+
+impl<const N: usize> Default for Emulator<N> {
+    fn default() -> Self {
+        Self::new(MappingVersion::default())
+    }
+}
+
+impl<const N: usize> Emulator<N> {
+    /// Emulator of the NDEF mapping `version` holding an empty NDEF file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is too small for the message length, or larger than the
+    /// NDEF file `version` can announce.
+    pub fn new(version: MappingVersion) -> Self {
+        assert!(
+            (version.length_len()..=version.max_file_size()).contains(&N),
+            "the NDEF file does not fit the mapping version"
+        );
+        Emulator {
+            version,
+            selected: Selected::Nothing,
+            file: [0; N],
+            read_only: false,
+            written: false,
+        }
+    }
+
+    /// Serves `message` from now on, see [`Emulator::set_ndef`].
+    pub fn set_message(&mut self, message: &Message<'_>) -> Result<()> {
+        self.set_ndef(&message.to_vec()?)
+    }
+
+    /// Serves `ndef`, a serialized NDEF message, from now on. It is refused
+    /// with [`Error::BufferTooSmall`] when it does not fit the NDEF file.
+    pub fn set_ndef(&mut self, ndef: &[u8]) -> Result<()> {
+        let start = self.version.length_len();
+        if ndef.len() > N - start {
+            return Err(Error::BufferTooSmall);
+        }
+        self.file[start..start + ndef.len()].copy_from_slice(ndef);
+        self.set_length(ndef.len());
+        self.written = false;
+        Ok(())
+    }
+
+    /// Serialized NDEF message of the NDEF file, as set or last written by the
+    /// reader, to be parsed with [`Message::try_from`]. It is empty while a
+    /// reader writes a message, and reported as [`Error::SliceTooShort`] when
+    /// the reader left a length longer than the file.
+    pub fn ndef(&self) -> Result<&[u8]> {
+        let start = self.version.length_len();
+        let len = self.length();
+        self.file
+            .get(start..start.saturating_add(len))
+            .ok_or(Error::SliceTooShort)
+    }
+
+    /// Whether a reader wrote a new message since the last call, which then
+    /// returns `false` until the next one.
+    pub fn take_written(&mut self) -> bool {
+        core::mem::take(&mut self.written)
+    }
+
+    /// Refuses or accepts the UPDATE BINARY commands of readers, whose
+    /// capability container announces it.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Content of the capability container file.
+    pub fn capability_container(&self) -> CapabilityContainer {
+        CapabilityContainer {
+            version: self.version,
+            mle: MAX_LE as u16,
+            mlc: MAX_LE as u16,
+            file_id: NDEF_FILE_ID,
+            max_ndef_size: N as u32,
+            read_access: ACCESS_GRANTED,
+            write_access: if self.read_only {
+                NO_ACCESS
+            } else {
+                ACCESS_GRANTED
+            },
+        }
+    }
+
+    /// Answers the command APDU `apdu`. Any error is answered with its status
+    /// word, so the reader always gets a response.
+    pub fn process(&mut self, apdu: &[u8]) -> Response {
+        let mut response = Response::new();
+        let status = match Command::parse(apdu) {
+            None => StatusWord::WRONG_LENGTH,
+            Some(command) => self.answer(&command, &mut response),
+        };
+        if status != StatusWord::OK {
+            response.clear();
+        }
+        // the data is at most `MAX_LE` bytes, which leaves room for the status
+        let _ = response.extend_from_slice(&status.to_bytes());
+        response
+    }
+
+    fn answer(&mut self, command: &Command<'_>, response: &mut Response) -> StatusWord {
+        if command.cla != CLA {
+            return StatusWord::CLA_NOT_SUPPORTED;
+        }
+        let v3 = self.version == MappingVersion::V3;
+        match command.ins {
+            INS_SELECT => self.select(command),
+            INS_READ_BINARY if command.p1 & 0x80 == 0 => {
+                let offset = usize::from(u16::from_be_bytes([command.p1, command.p2]));
+                let Some(le) = command.le else {
+                    return StatusWord::WRONG_LENGTH;
+                };
+                self.read(offset, le.min(MAX_LE), response)
+            }
+            INS_READ_BINARY_ODO if v3 => {
+                let (Some(offset), Some(le)) = (odo_offset(command), command.le) else {
+                    return StatusWord::WRONG_DATA;
+                };
+                // the data comes in a discretionary data object
+                let mut len = le.min(MAX_LE).saturating_sub(2);
+                if len >= 0x80 {
+                    len -= 1;
+                }
+                let _ = response.push(DDO_TAG);
+                let header = response.len();
+                let status = self.read(offset, len, response);
+                let data_len = response.len() - header;
+                let _ = response.insert(header, data_len as u8);
+                if ber_length_len(data_len) == 2 {
+                    let _ = response.insert(header, 0x81);
+                }
+                status
+            }
+            INS_UPDATE_BINARY if command.p1 & 0x80 == 0 => {
+                let offset = usize::from(u16::from_be_bytes([command.p1, command.p2]));
+                self.update(offset, command.data)
+            }
+            INS_UPDATE_BINARY_ODO if v3 => {
+                let Some(offset) = odo_offset(command) else {
+                    return StatusWord::WRONG_DATA;
+                };
+                let ddo = &command.data[5..];
+                let Some((_, data)) = ddo
+                    .split_first()
+                    .filter(|(tag, _)| **tag == DDO_TAG)
+                    .and_then(|(_, rest)| read_ber_length(rest))
+                    .filter(|(len, data)| *len == data.len())
+                else {
+                    return StatusWord::WRONG_DATA;
+                };
+                self.update(offset, data)
+            }
+            INS_READ_BINARY | INS_UPDATE_BINARY => StatusWord::WRONG_PARAMETERS,
+            _ => StatusWord::INS_NOT_SUPPORTED,
+        }
+    }
+
+    fn select(&mut self, command: &Command<'_>) -> StatusWord {
+        let parameters = [command.p1, command.p2];
+        let (selected, status) = if parameters == SELECT_BY_NAME {
+            if command.data == NDEF_AID {
+                (Selected::Application, StatusWord::OK)
+            } else {
+                (Selected::Nothing, StatusWord::NOT_FOUND)
+            }
+        } else if parameters == SELECT_BY_ID {
+            // files are only found in the NDEF Tag Application
+            let id = <[u8; 2]>::try_from(command.data).map(u16::from_be_bytes);
+            match (self.selected, id) {
+                (Selected::Nothing, _) => (Selected::Nothing, StatusWord::NOT_FOUND),
+                (_, Ok(CC_FILE_ID)) => (Selected::CcFile, StatusWord::OK),
+                (_, Ok(NDEF_FILE_ID)) => (Selected::NdefFile, StatusWord::OK),
+                _ => (Selected::Application, StatusWord::NOT_FOUND),
+            }
+        } else {
+            return StatusWord::INCORRECT_P1_P2;
+        };
+        self.selected = selected;
+        status
+    }
+
+    /// Appends at most `len` bytes of the selected file from `offset` to
+    /// `response`.
+    fn read(&self, offset: usize, len: usize, response: &mut Response) -> StatusWord {
+        let cc = self.capability_container().to_bytes();
+        let file = match self.selected {
+            Selected::CcFile => &cc[..],
+            Selected::NdefFile => &self.file[..],
+            _ => return StatusWord::NOT_ALLOWED,
+        };
+        if offset > file.len() {
+            return StatusWord::WRONG_PARAMETERS;
+        }
+        let end = file.len().min(offset + len);
+        let _ = response.extend_from_slice(&file[offset..end]);
+        StatusWord::OK
+    }
+
+    /// Writes `data` at `offset` of the NDEF file.
+    fn update(&mut self, offset: usize, data: &[u8]) -> StatusWord {
+        match self.selected {
+            Selected::NdefFile if self.read_only => return StatusWord::SECURITY_NOT_SATISFIED,
+            Selected::NdefFile => {}
+            Selected::CcFile => return StatusWord::SECURITY_NOT_SATISFIED,
+            _ => return StatusWord::NOT_ALLOWED,
+        }
+        if data.len() > MAX_LE {
+            return StatusWord::WRONG_LENGTH;
+        }
+        let Some(target) = self.file.get_mut(offset..offset + data.len()) else {
+            return StatusWord::WRONG_PARAMETERS;
+        };
+        target.copy_from_slice(data);
+        // a reader writes the length last, once the message is in place
+        if offset < self.version.length_len() && self.length() != 0 {
+            self.written = true;
+        }
+        StatusWord::OK
+    }
+
+    /// Message length at the start of the NDEF file.
+    fn length(&self) -> usize {
+        let mut len = 0;
+        for byte in &self.file[..self.version.length_len()] {
+            len = (len << 8) | usize::from(*byte);
+        }
+        len
+    }
+
+    fn set_length(&mut self, len: usize) {
+        let length_len = self.version.length_len();
+        let bytes = (len as u32).to_be_bytes();
+        self.file[..length_len].copy_from_slice(&bytes[4 - length_len..]);
+    }
+}
+
+/// Offset given by the offset data object at the start of the data of a READ
+/// or UPDATE BINARY command with an ODO, whose P1 and P2 must be zero.
+fn odo_offset(command: &Command<'_>) -> Option<usize> {
+    match *command.data {
+        [ODO_TAG, 3, o0, o1, o2, ..] if command.p1 == 0 && command.p2 == 0 => {
+            Some((usize::from(o0) << 16) | (usize::from(o1) << 8) | usize::from(o2))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SELECT_APP: [u8; 13] = [
+        0x00, 0xA4, 0x04, 0x00, 0x07, 0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01, 0x00,
+    ];
+    const SELECT_CC: [u8; 7] = [0x00, 0xA4, 0x00, 0x0C, 0x02, 0xE1, 0x03];
+    const SELECT_NDEF: [u8; 7] = [0x00, 0xA4, 0x00, 0x0C, 0x02, 0xE1, 0x04];
+    const OK: [u8; 2] = [0x90, 0x00];
+
+    fn ok(data: &[u8]) -> Response {
+        let mut response = Response::new();
+        response.extend_from_slice(data).unwrap();
+        response.extend_from_slice(&OK).unwrap();
+        response
+    }
+
+    fn status(status: StatusWord) -> Response {
+        Response::from_slice(&status.to_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_reader_reads_message() {
+        let msg = Message::builder()
+            .uri("https://example.com")
+            .build()
+            .unwrap();
+        let ndef = msg.to_vec().unwrap();
+        let mut emulator = Emulator::<64>::default();
+        emulator.set_message(&msg).unwrap();
+
+        assert_eq!(emulator.process(&SELECT_APP), ok(&[]));
+        assert_eq!(emulator.process(&SELECT_CC), ok(&[]));
+        assert_eq!(
+            emulator.process(&[0x00, 0xB0, 0x00, 0x00, 0x0F]),
+            ok(&[
+                0x00, 0x0F, 0x20, 0x00, 0xFF, 0x00, 0xFF, 0x04, 0x06, 0xE1, 0x04, 0x00, 0x40, 0x00,
+                0x00
+            ])
+        );
+        assert_eq!(emulator.process(&SELECT_NDEF), ok(&[]));
+        assert_eq!(
+            emulator.process(&[0x00, 0xB0, 0x00, 0x00, 0x02]),
+            ok(&[0x00, ndef.len() as u8])
+        );
+        let response = emulator.process(&[0x00, 0xB0, 0x00, 0x02, ndef.len() as u8]);
+        assert_eq!(response, ok(&ndef));
+        assert_eq!(Message::try_from(&response[..ndef.len()]).unwrap(), msg);
+
+        // reading stops at the end of the file
+        assert_eq!(emulator.process(&[0x00, 0xB0, 0x00, 0x3E, 0x00]).len(), 4);
+        assert_eq!(
+            emulator.process(&[0x00, 0xB0, 0x00, 0x41, 0x01]),
+            status(StatusWord::WRONG_PARAMETERS)
+        );
+    }
+
+    #[test]
+    fn test_reader_writes_message() {
+        let msg = Message::builder().text("en", "written").build().unwrap();
+        let ndef = msg.to_vec().unwrap();
+        let mut emulator = Emulator::<64>::default();
+        for apdu in [&SELECT_APP[..], &SELECT_NDEF] {
+            assert_eq!(emulator.process(apdu), ok(&[]));
+        }
+
+        let mut update = [0u8; 5 + 62];
+        update[..5].copy_from_slice(&[0x00, 0xD6, 0x00, 0x00, 0x02]);
+        assert_eq!(emulator.process(&update[..7]), ok(&[]));
+        update[..5].copy_from_slice(&[0x00, 0xD6, 0x00, 0x02, ndef.len() as u8]);
+        update[5..5 + ndef.len()].copy_from_slice(&ndef);
+        assert_eq!(emulator.process(&update[..5 + ndef.len()]), ok(&[]));
+        assert!(emulator.ndef().unwrap().is_empty());
+        assert!(!emulator.take_written());
+        let nlen = [0x00, 0xD6, 0x00, 0x00, 0x02, 0x00, ndef.len() as u8];
+        assert_eq!(emulator.process(&nlen), ok(&[]));
+
+        assert!(emulator.take_written());
+        assert!(!emulator.take_written());
+        assert_eq!(Message::try_from(emulator.ndef().unwrap()).unwrap(), msg);
+
+        emulator.set_read_only(true);
+        assert_eq!(
+            emulator.process(&nlen),
+            status(StatusWord::SECURITY_NOT_SATISFIED)
+        );
+        assert_eq!(emulator.capability_container().write_access, NO_ACCESS);
+    }
+
+    #[test]
+    fn test_mapping_version_3() {
+        let mut emulator = Emulator::<300>::new(MappingVersion::V3);
+        emulator.set_ndef(&[0xAA; 200]).unwrap();
+        for apdu in [&SELECT_APP[..], &SELECT_CC] {
+            assert_eq!(emulator.process(apdu), ok(&[]));
+        }
+        let cc = emulator.process(&[0x00, 0xB0, 0x00, 0x00, 0x11]);
+        assert_eq!(
+            CapabilityContainer::from_bytes(&cc[..17]),
+            Ok(emulator.capability_container())
+        );
+        assert_eq!(
+            &cc[7..15],
+            &[0x06, 0x08, 0xE1, 0x04, 0x00, 0x00, 0x01, 0x2C]
+        );
+
+        assert_eq!(emulator.process(&SELECT_NDEF), ok(&[]));
+        assert_eq!(
+            emulator.process(&[0x00, 0xB0, 0x00, 0x00, 0x04]),
+            ok(&[0x00, 0x00, 0x00, 200])
+        );
+        // 200 bytes from offset 4 take a two byte DDO length
+        let read = [
+            0x00, 0xB1, 0x00, 0x00, 0x05, 0x54, 0x03, 0x00, 0x00, 0x04, 0x00,
+        ];
+        let response = emulator.process(&read);
+        assert_eq!(&response[..3], &[0x53, 0x81, 252]);
+        assert_eq!(response.len(), 3 + 252 + 2);
+
+        let update = [
+            0x00, 0xD7, 0x00, 0x00, 0x08, 0x54, 0x03, 0x00, 0x00, 0x04, 0x53, 0x01, 0x55,
+        ];
+        assert_eq!(emulator.process(&update), ok(&[]));
+        assert_eq!(emulator.ndef().unwrap()[..2], [0x55, 0xAA]);
+        let mut update = update;
+        update[11] = 0x02;
+        assert_eq!(emulator.process(&update), status(StatusWord::WRONG_DATA));
+    }
+
+    #[test]
+    fn test_command_errors() {
+        let mut emulator = Emulator::<64>::default();
+        let read = [0x00, 0xB0, 0x00, 0x00, 0x02];
+        assert_eq!(emulator.process(&read), status(StatusWord::NOT_ALLOWED));
+        assert_eq!(emulator.process(&SELECT_CC), status(StatusWord::NOT_FOUND));
+        assert_eq!(
+            emulator.process(&[0x00, 0xA4, 0x04, 0x00, 0x02, 0xD2, 0x76]),
+            status(StatusWord::NOT_FOUND)
+        );
+        assert_eq!(emulator.process(&SELECT_APP), ok(&[]));
+        assert_eq!(
+            emulator.process(&[0x00, 0xA4, 0x00, 0x0C, 0x02, 0xE1, 0x05]),
+            status(StatusWord::NOT_FOUND)
+        );
+        assert_eq!(emulator.process(&SELECT_CC), ok(&[]));
+        assert_eq!(
+            emulator.process(&[0x00, 0xD6, 0x00, 0x00, 0x01, 0x00]),
+            status(StatusWord::SECURITY_NOT_SATISFIED)
+        );
+        assert_eq!(
+            emulator.process(&[0x80, 0xB0, 0x00, 0x00, 0x02]),
+            status(StatusWord::CLA_NOT_SUPPORTED)
+        );
+        // commands with an ODO come with version 3.0
+        assert_eq!(
+            emulator.process(&[0x00, 0xB1, 0x00, 0x00, 0x05, 0x54, 0x03, 0x00, 0x00, 0x00, 0x00]),
+            status(StatusWord::INS_NOT_SUPPORTED)
+        );
+        assert_eq!(
+            emulator.process(&[0x00, 0xB0, 0x00]),
+            status(StatusWord::WRONG_LENGTH)
+        );
+    }
+}
@@ -0,0 +1,537 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::{
+    ber_length_len, read_ber_length, CapabilityContainer, Emulator, MappingVersion, StatusWord,
+    CC_FILE_ID, CLA, DDO_TAG, INS_READ_BINARY, INS_READ_BINARY_ODO, INS_SELECT, INS_UPDATE_BINARY,
+    INS_UPDATE_BINARY_ODO, NDEF_AID, ODO_TAG, SELECT_BY_ID, SELECT_BY_NAME,
+};
+use crate::{write_all, Buffer, Error, Message};
+
+/// Most data bytes of a short APDU.
+const SHORT_LEN: usize = 0xFF;
+/// Longest response the reader takes: 256 bytes of data and the status word.
+const RESPONSE_LEN: usize = 256 + 2;
+/// Bytes of the offset data object of a command with an ODO.
+const ODO_LEN: usize = 5;
+/// Largest offset READ and UPDATE BINARY reach without an ODO.
+const MAX_SHORT_OFFSET: usize = 0x7FFF;
+
+type Apdu = heapless::Vec<u8, { 5 + SHORT_LEN + 1 }>;
+
+/// Link to an ISO-DEP card, which sends a command APDU and receives the
+/// response APDU. Errors of this crate convert into its error, so that the
+/// [`Reader`] reports both through it.
+pub trait Transceive {
+    type Error: From<Error>;
+
+    /// Sends `command` and writes the response, status word included, at the
+    /// start of `response`, returning its length.
+    fn transceive(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// Reader side of the NDEF Tag Application: detects the NDEF file of a Type 4
+/// Tag, then reads or writes its message in as many READ BINARY and UPDATE
+/// BINARY commands as the limits of the capability container require.
+///
+/// ```
+/// use ndef::{type4::{Emulator, Reader}, Message};
+///
+/// let msg = Message::builder().text("en", "hi").build().unwrap();
+/// let mut reader = Reader::new(Emulator::<128>::default());
+/// reader.write_message(&msg).unwrap();
+///
+/// let ndef = reader.read_ndef().unwrap();
+/// assert_eq!(Message::try_from(&ndef[..]).unwrap(), msg);
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Reader<T> {
+    transceiver: T,
+    cc: Option<CapabilityContainer>,
+}
+
+impl<T: Transceive> Reader<T> {
+    pub fn new(transceiver: T) -> Self {
+        Reader {
+            transceiver,
+            cc: None,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transceiver
+    }
+
+    /// Runs the NDEF detection procedure: selects the NDEF Tag Application,
+    /// reads the capability container file and selects the NDEF file. It is
+    /// run by the first read or write, and again after a failed one.
+    pub fn detect(&mut self) -> Result<CapabilityContainer, T::Error> {
+        self.cc = None;
+        let mut select = Apdu::new();
+        push(&mut select, &[CLA, INS_SELECT])?;
+        push(&mut select, &SELECT_BY_NAME)?;
+        push(&mut select, &[NDEF_AID.len() as u8])?;
+        push(&mut select, &NDEF_AID)?;
+        push(&mut select, &[0x00])?;
+        self.command(&select, &mut [0; RESPONSE_LEN])?;
+
+        self.select_file(CC_FILE_ID)?;
+        let mut cc = [0; RESPONSE_LEN];
+        let len = self.read_binary(0, 15, &mut cc)?;
+        let cc_len = match cc[..len] {
+            [len0, len1, ..] => usize::from(u16::from_be_bytes([len0, len1])),
+            _ => return Err(Error::SliceTooShort.into()),
+        };
+        // a capability container longer than a response is refused below
+        let cc_len = cc_len.min(cc.len() - 2);
+        let mut len = len.min(cc_len);
+        while len < cc_len {
+            let mut rest = [0; RESPONSE_LEN];
+            let n = self.read_binary(len, cc_len - len, &mut rest)?;
+            if n == 0 {
+                break;
+            }
+            cc[len..len + n].copy_from_slice(&rest[..n]);
+            len += n;
+        }
+        let cc = CapabilityContainer::from_bytes(&cc[..len])?;
+        if !cc.is_readable() || cc.mle == 0 || cc.mlc == 0 {
+            return Err(Error::InvalidCapabilityContainer.into());
+        }
+        self.select_file(cc.file_id)?;
+        self.cc = Some(cc);
+        Ok(cc)
+    }
+
+    /// Serialized NDEF message of the tag, to be parsed with
+    /// [`Message::try_from`]. It is empty on a tag that holds none.
+    pub fn read_ndef(&mut self) -> Result<Buffer, T::Error> {
+        let cc = self.detected()?;
+        let length_len = cc.version.length_len();
+        let mut length = [0; RESPONSE_LEN];
+        if self.read_binary(0, length_len, &mut length)? < length_len {
+            return Err(Error::SliceTooShort.into());
+        }
+        let len = length[..length_len]
+            .iter()
+            .fold(0, |len, byte| (len << 8) | usize::from(*byte));
+        if len > (cc.max_ndef_size as usize).saturating_sub(length_len) {
+            return Err(Error::SliceTooShort.into());
+        }
+        let mut ndef = Buffer::new();
+        while ndef.len() < len {
+            let mut chunk = [0; RESPONSE_LEN];
+            let n = self.read_binary(length_len + ndef.len(), len - ndef.len(), &mut chunk)?;
+            if n == 0 {
+                return Err(Error::SliceTooShort.into());
+            }
+            write_all(&mut ndef, &chunk[..n])?;
+        }
+        Ok(ndef)
+    }
+
+    /// Message of the tag, parsed and detached from the bytes it was read in.
+    #[cfg(feature = "alloc")]
+    pub fn read_message(&mut self) -> Result<Message<'static>, T::Error> {
+        let ndef = self.read_ndef()?;
+        let message = Message::try_from(&ndef[..]).map_err(Error::from)?;
+        Ok(message.into_owned())
+    }
+
+    /// Writes `ndef`, a serialized NDEF message, so that a reader never finds a
+    /// partly written one: the message length is set to 0, the message is
+    /// written, then its length. A tag whose capability container denies
+    /// writing is reported as [`Error::ReadOnly`], and a message that does not
+    /// fit its NDEF file as [`Error::BufferTooSmall`].
+    pub fn write_ndef(&mut self, ndef: &[u8]) -> Result<(), T::Error> {
+        let cc = self.detected()?;
+        if !cc.is_writable() {
+            return Err(Error::ReadOnly.into());
+        }
+        let length_len = cc.version.length_len();
+        if ndef.len() > (cc.max_ndef_size as usize).saturating_sub(length_len) {
+            return Err(Error::BufferTooSmall.into());
+        }
+        let length = (ndef.len() as u32).to_be_bytes();
+        let length = &length[4 - length_len..];
+        self.update_binary(0, &[0; 4][..length_len])?;
+        let mut written = 0;
+        while written < ndef.len() {
+            written += self.update_binary(length_len + written, &ndef[written..])?;
+        }
+        self.update_binary(0, length)?;
+        Ok(())
+    }
+
+    /// Writes `message`, see [`Reader::write_ndef`].
+    pub fn write_message(&mut self, message: &Message<'_>) -> Result<(), T::Error> {
+        self.write_ndef(&message.to_vec()?)
+    }
+
+    /// Capability container of the detected tag, detecting it first if needed.
+    fn detected(&mut self) -> Result<CapabilityContainer, T::Error> {
+        match self.cc {
+            Some(cc) => Ok(cc),
+            None => self.detect(),
+        }
+    }
+
+    /// Sends `command`, and returns the length of the data of the response,
+    /// now at the start of `response`. A status word other than 90 00 is
+    /// reported as [`Error::UnexpectedStatus`], after which the tag is
+    /// detected again.
+    fn command(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, T::Error> {
+        // forget the tag until it is detected again, unless the command succeeds
+        let cc = self.cc.take();
+        let len = self.transceiver.transceive(command, response)?;
+        let (data, status) = match response.get(..len) {
+            Some([data @ .., sw1, sw2]) => {
+                (data.len(), StatusWord(u16::from_be_bytes([*sw1, *sw2])))
+            }
+            _ => return Err(Error::SliceTooShort.into()),
+        };
+        if status != StatusWord::OK {
+            return Err(Error::UnexpectedStatus(status.0).into());
+        }
+        self.cc = cc;
+        Ok(data)
+    }
+
+    fn select_file(&mut self, id: u16) -> Result<(), T::Error> {
+        let mut select = Apdu::new();
+        push(&mut select, &[CLA, INS_SELECT])?;
+        push(&mut select, &SELECT_BY_ID)?;
+        push(&mut select, &[2])?;
+        push(&mut select, &id.to_be_bytes())?;
+        self.command(&select, &mut [0; RESPONSE_LEN])?;
+        Ok(())
+    }
+
+    /// Reads at most `len` bytes of the selected file from `offset` into
+    /// `data`, in a single command, returning how many were read.
+    fn read_binary(
+        &mut self,
+        offset: usize,
+        len: usize,
+        data: &mut [u8; RESPONSE_LEN],
+    ) -> Result<usize, T::Error> {
+        let mle = self.cc.map_or(SHORT_LEN, |cc| usize::from(cc.mle));
+        let le = mle.min(SHORT_LEN + 1);
+        let mut command = Apdu::new();
+        if offset <= MAX_SHORT_OFFSET {
+            let le = le.min(len);
+            push(&mut command, &[CLA, INS_READ_BINARY])?;
+            push(&mut command, &(offset as u16).to_be_bytes())?;
+            push(&mut command, &[le as u8])?;
+            // a card that answers with more than it was asked for is cut short
+            return Ok(self.command(&command, data)?.min(le));
+        }
+        if self.cc.map(|cc| cc.version) != Some(MappingVersion::V3) {
+            return Err(Error::FieldTooLong.into());
+        }
+        // the response carries the data in a discretionary data object
+        let le = le.min(len + 1 + ber_length_len(len));
+        push(
+            &mut command,
+            &[CLA, INS_READ_BINARY_ODO, 0, 0, ODO_LEN as u8],
+        )?;
+        push(&mut command, &odo(offset)?)?;
+        push(&mut command, &[le as u8])?;
+        let n = self.command(&command, data)?;
+        let (tag, rest) = data[..n].split_first().ok_or(Error::SliceTooShort)?;
+        let (len, value) = read_ber_length(rest)
+            .filter(|(len, value)| *tag == DDO_TAG && *len <= value.len())
+            .ok_or(Error::SliceTooShort)?;
+        let start = n - value.len();
+        let len = len.min(le);
+        data.copy_within(start..start + len, 0);
+        Ok(len)
+    }
+
+    /// Writes the start of `data` at `offset` of the selected file in a single
+    /// command, returning how many bytes were written.
+    fn update_binary(&mut self, offset: usize, data: &[u8]) -> Result<usize, T::Error> {
+        let mlc = self.cc.map_or(SHORT_LEN, |cc| usize::from(cc.mlc));
+        let lc = mlc.min(SHORT_LEN);
+        let mut command = Apdu::new();
+        if offset <= MAX_SHORT_OFFSET {
+            let data = &data[..data.len().min(lc)];
+            push(&mut command, &[CLA, INS_UPDATE_BINARY])?;
+            push(&mut command, &(offset as u16).to_be_bytes())?;
+            push(&mut command, &[data.len() as u8])?;
+            push(&mut command, data)?;
+            self.command(&command, &mut [0; RESPONSE_LEN])?;
+            return Ok(data.len());
+        }
+        if self.cc.map(|cc| cc.version) != Some(MappingVersion::V3) {
+            return Err(Error::FieldTooLong.into());
+        }
+        // the data comes in a discretionary data object after the offset
+        // and its tag, where its BER length and the data share what is left
+        let room = lc.saturating_sub(ODO_LEN + 1);
+        let len = data.len().min(match room {
+            0..=0x80 => room.saturating_sub(1),
+            _ => room - 2,
+        });
+        if len == 0 {
+            // MLc leaves no room for data after the offset
+            return Err(Error::InvalidCapabilityContainer.into());
+        }
+        let data = &data[..len];
+        let lc = ODO_LEN + 1 + ber_length_len(len) + len;
+        debug_assert!(lc <= mlc, "Lc {lc} exceeds MLc {mlc}");
+        push(&mut command, &[CLA, INS_UPDATE_BINARY_ODO, 0, 0, lc as u8])?;
+        push(&mut command, &odo(offset)?)?;
+        push(&mut command, &[DDO_TAG])?;
+        if ber_length_len(len) == 2 {
+            push(&mut command, &[0x81])?;
+        }
+        push(&mut command, &[len as u8])?;
+        push(&mut command, data)?;
+        self.command(&command, &mut [0; RESPONSE_LEN])?;
+        Ok(len)
+    }
+}
+
+fn push(apdu: &mut Apdu, bytes: &[u8]) -> Result<(), Error> {
+    apdu.extend_from_slice(bytes)
+        .map_err(|_| Error::BufferTooSmall)
+}
+
+/// Offset data object of a READ or UPDATE BINARY command with an ODO.
+fn odo(offset: usize) -> Result<[u8; ODO_LEN], Error> {
+    let offset = u32::try_from(offset)
+        .ok()
+        .filter(|offset| *offset <= 0xFF_FFFF)
+        .ok_or(Error::FieldTooLong)?
+        .to_be_bytes();
+    Ok([ODO_TAG, 3, offset[1], offset[2], offset[3]])
+}
+
+/// The emulator answers a [`Reader`] directly, to try out the two sides of
+/// the NDEF Tag Application without a card.
+impl<const N: usize> Transceive for Emulator<N> {
+    type Error = Error;
+
+    fn transceive(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Error> {
+        let answer = self.process(command);
+        response
+            .get_mut(..answer.len())
+            .ok_or(Error::BufferTooSmall)?
+            .copy_from_slice(&answer);
+        Ok(answer.len())
+    }
+}
+
+/// Transceiver for tests, which checks each command against a script of
+/// command and response APDUs and answers with the scripted response.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MockTransceiver<'a> {
+    script: &'a [(&'a [u8], &'a [u8])],
+    next: usize,
+}
+
+impl<'a> MockTransceiver<'a> {
+    pub fn new(script: &'a [(&'a [u8], &'a [u8])]) -> Self {
+        MockTransceiver { script, next: 0 }
+    }
+
+    /// Whether every scripted command was sent.
+    pub fn is_done(&self) -> bool {
+        self.next == self.script.len()
+    }
+}
+
+impl Transceive for MockTransceiver<'_> {
+    type Error = Error;
+
+    /// # Panics
+    ///
+    /// Panics if `command` is not the next command of the script, or the
+    /// script is over.
+    fn transceive(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Error> {
+        let Some((expected, answer)) = self.script.get(self.next) else {
+            panic!("unscripted command {command:02X?}");
+        };
+        assert_eq!(
+            command, *expected,
+            "command {} differs from the script",
+            self.next
+        );
+        self.next += 1;
+        response
+            .get_mut(..answer.len())
+            .ok_or(Error::BufferTooSmall)?
+            .copy_from_slice(answer);
+        Ok(answer.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SELECT_APP: &[u8] = &[
+        0x00, 0xA4, 0x04, 0x00, 0x07, 0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01, 0x00,
+    ];
+    const SELECT_CC: &[u8] = &[0x00, 0xA4, 0x00, 0x0C, 0x02, 0xE1, 0x03];
+    const SELECT_NDEF: &[u8] = &[0x00, 0xA4, 0x00, 0x0C, 0x02, 0xE1, 0x04];
+    const OK: &[u8] = &[0x90, 0x00];
+
+    /// A tag with small limits makes the reader split its commands.
+    #[test]
+    fn test_chunked_read_and_write() {
+        let cc = &[
+            0x00, 0x0F, 0x20, 0x00, 0x04, 0x00, 0x03, 0x04, 0x06, 0xE1, 0x04, 0x00, 0x20, 0x00,
+            0x00, 0x90, 0x00,
+        ];
+        let detection: [(&[u8], &[u8]); 4] = [
+            (SELECT_APP, OK),
+            (SELECT_CC, OK),
+            (&[0x00, 0xB0, 0x00, 0x00, 0x0F], cc),
+            (SELECT_NDEF, OK),
+        ];
+        let ndef = [0xD0, 0x00, 0x00, 0x90, 0x00, 0x00];
+        let mut script = heapless::Vec::<(&[u8], &[u8]), 16>::new();
+        script.extend_from_slice(&detection).unwrap();
+        script
+            .extend_from_slice(&[
+                (&[0x00, 0xB0, 0x00, 0x00, 0x02], &[0x00, 0x06, 0x90, 0x00]),
+                (
+                    &[0x00, 0xB0, 0x00, 0x02, 0x04],
+                    &[0xD0, 0x00, 0x00, 0x90, 0x90, 0x00],
+                ),
+                (&[0x00, 0xB0, 0x00, 0x06, 0x02], &[0x00, 0x00, 0x90, 0x00]),
+                (&[0x00, 0xD6, 0x00, 0x00, 0x02, 0x00, 0x00], OK),
+                (&[0x00, 0xD6, 0x00, 0x02, 0x03, 0xD0, 0x00, 0x00], OK),
+                (&[0x00, 0xD6, 0x00, 0x05, 0x03, 0x90, 0x00, 0x00], OK),
+                (&[0x00, 0xD6, 0x00, 0x00, 0x02, 0x00, 0x06], OK),
+            ])
+            .unwrap();
+
+        let mut reader = Reader::new(MockTransceiver::new(&script));
+        assert_eq!(&reader.read_ndef().unwrap()[..], &ndef);
+        reader.write_ndef(&ndef).unwrap();
+        assert!(reader.into_inner().is_done());
+    }
+
+    #[test]
+    fn test_errors() {
+        let script: [(&[u8], &[u8]); 1] = [(SELECT_APP, &[0x6A, 0x82])];
+        let mut reader = Reader::new(MockTransceiver::new(&script));
+        assert_eq!(reader.read_ndef(), Err(Error::UnexpectedStatus(0x6A82)));
+
+        let mut emulator = Emulator::<64>::default();
+        emulator.set_read_only(true);
+        let mut reader = Reader::new(emulator);
+        assert_eq!(reader.write_ndef(&[0xD0, 0, 0]), Err(Error::ReadOnly));
+        let mut reader = Reader::new(Emulator::<8>::default());
+        assert_eq!(reader.write_ndef(&[0; 7]), Err(Error::BufferTooSmall));
+    }
+
+    /// A card answering with more than it was asked for, announcing a
+    /// capability container of 256 bytes.
+    #[test]
+    fn test_oversized_responses() {
+        let cc = &[
+            0x01, 0x00, 0x20, 0x00, 0xFF, 0x00, 0xFF, 0x04, 0x06, 0xE1, 0x04, 0x00, 0x80, 0x00,
+            0x00, 0x90, 0x00,
+        ];
+        let mut flood = [0xEE; 256 + 2];
+        flood[256..].copy_from_slice(OK);
+        let script: [(&[u8], &[u8]); 7] = [
+            (SELECT_APP, OK),
+            (SELECT_CC, OK),
+            (&[0x00, 0xB0, 0x00, 0x00, 0x0F], cc),
+            (&[0x00, 0xB0, 0x00, 0x0F, 0xF1], &flood),
+            (SELECT_NDEF, OK),
+            (
+                &[0x00, 0xB0, 0x00, 0x00, 0x02],
+                &[0x00, 0x03, 0xD0, 0x90, 0x00],
+            ),
+            (&[0x00, 0xB0, 0x00, 0x02, 0x03], &flood),
+        ];
+        let mut reader = Reader::new(MockTransceiver::new(&script));
+        assert_eq!(&reader.read_ndef().unwrap()[..], &[0xEE; 3]);
+        assert!(reader.into_inner().is_done());
+    }
+
+    /// Transceiver accepting every command, which keeps the last one.
+    #[derive(Default)]
+    struct Sink(heapless::Vec<u8, { 5 + SHORT_LEN + 1 }>);
+
+    impl Transceive for Sink {
+        type Error = Error;
+
+        fn transceive(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Error> {
+            self.0.clear();
+            self.0.extend_from_slice(command).unwrap();
+            response[..2].copy_from_slice(OK);
+            Ok(2)
+        }
+    }
+
+    /// Reader of a version 3.0 tag taking commands of at most `mlc` bytes.
+    fn reader_with_mlc(mlc: u16) -> Reader<Sink> {
+        let mut reader = Reader::new(Sink::default());
+        reader.cc = Some(CapabilityContainer {
+            version: MappingVersion::V3,
+            mle: 0xFF,
+            mlc,
+            file_id: 0xE104,
+            max_ndef_size: 0x10000,
+            read_access: 0,
+            write_access: 0,
+        });
+        reader
+    }
+
+    /// An MLc too small for an ODO and a data byte cannot reach past 32767.
+    #[test]
+    fn test_mlc_without_room() {
+        for mlc in [6, 7] {
+            assert_eq!(
+                reader_with_mlc(mlc).update_binary(0x8000, &[1, 2, 3]),
+                Err(Error::InvalidCapabilityContainer)
+            );
+        }
+    }
+
+    /// Past 32767, an UPDATE BINARY fills MLc with the ODO, the tag and
+    /// length of the data and as much data as fits, whatever is left to write.
+    #[test]
+    fn test_update_binary_odo_fills_mlc() {
+        let data = [0x5A; 0x8000];
+        for (mlc, len) in [(8, 1), (0x7F, 120), (0x86, 127), (0x87, 127), (0xFF, 247)] {
+            let mut reader = reader_with_mlc(mlc);
+            assert_eq!(reader.update_binary(0x8000, &data), Ok(len));
+            let command = reader.into_inner().0;
+            let lc = usize::from(command[4]);
+            assert_eq!(command.len(), 5 + lc);
+            assert!(lc <= usize::from(mlc), "Lc {lc} exceeds MLc {mlc}");
+            assert_eq!(&command[5..11], &[0x54, 0x03, 0x00, 0x80, 0x00, 0x53]);
+        }
+    }
+
+    /// Version 3.0 reaches offsets past 32767 with commands carrying an ODO.
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_large_file() {
+        let ndef = alloc::vec![0x5A; 0x8100];
+        let mut reader = Reader::new(Emulator::<0x8200>::new(MappingVersion::V3));
+        reader.write_ndef(&ndef).unwrap();
+        assert_eq!(reader.read_ndef().unwrap(), ndef);
+
+        let msg = Message::builder().text("en", "hi").build().unwrap();
+        reader.write_message(&msg).unwrap();
+        assert_eq!(reader.read_message().unwrap(), msg);
+
+        // thousands of bytes past 32767 take full UPDATE BINARY commands
+        let ndef = alloc::vec![0xA5; 0x8E00];
+        let mut reader = Reader::new(Emulator::<0x9000>::new(MappingVersion::V3));
+        reader.write_ndef(&ndef).unwrap();
+        assert_eq!(reader.read_ndef().unwrap(), ndef);
+    }
+}
@@ -0,0 +1,295 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Memory of an NFC Forum Type 5 Tag, an ISO 15693 vicinity tag such as an
+//! ICODE SLIX or an ST25DV: a capability container of 4 bytes, or of 8 bytes
+//! for a data area larger than 2040 bytes, followed by the data area, whose
+//! TLVs are encoded as in a [Type 2 Tag](crate::type2).
+//!
+//! The functions of this module take the memory starting at block 0, where
+//! the capability container is.
+//!
+//! ```
+//! use ndef::{type5::{self, CapabilityContainer}, Message};
+//!
+//! let mut memory = [0; 64];
+//! let cc = CapabilityContainer::new(56).unwrap();
+//! memory[..4].copy_from_slice(&cc.to_bytes());
+//!
+//! let msg = Message::builder().text("en", "hi").build().unwrap();
+//! type5::write_message(&mut memory, &msg).unwrap();
+//!
+//! let ndef = type5::read_ndef(&memory).unwrap();
+//! assert_eq!(Message::try_from(&ndef[..]).unwrap(), msg);
+//! ```
+//!
+//! [`VirtualTag`] holds the blocks of a whole tag, to try out formatting,
+//! writing and locking without one.
+
+use core::ops::Range;
+
+use crate::{type2, Buffer, Error, Message, Result};
+
+mod tag;
+
+pub use tag::VirtualTag;
+
+/// Most bytes of a data area described by a 4 byte capability container.
+pub const MAX_SHORT_DATA_AREA: usize = 8 * 0xFF;
+
+/// Largest capability container, in bytes.
+pub const MAX_CC_LEN: usize = 8;
+
+/// Magic number of a tag read with the commands taking a 1 byte block number.
+const MAGIC: u8 = 0xE1;
+/// Magic number of a tag read with the extended commands, taking a 2 byte
+/// block number.
+const MAGIC_EXTENDED: u8 = 0xE2;
+
+const MULTIPLE_BLOCK_READ: u8 = 0x01;
+const LOCK_BLOCK: u8 = 0x08;
+const SPECIAL_FRAME: u8 = 0x10;
+
+/// Capability container of a Type 5 Tag, in its first bytes, which marks the
+/// tag as formatted for NDEF, tells whether its data area can be read and
+/// written, and which optional commands the tag supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CapabilityContainer {
+    /// Whether the tag is addressed with the extended commands.
+    pub extended_commands: bool,
+    /// Version of the mapping, major in the two high bits and minor in the
+    /// two bits after them; the low nibble is always 0.
+    pub version: u8,
+    /// Read access condition, 2 bits.
+    pub read_access: u8,
+    /// Write access condition, 2 bits.
+    pub write_access: u8,
+    /// Size of the data area, in units of 8 bytes.
+    pub mlen: u16,
+    /// Whether the capability container takes 8 bytes, which it must when
+    /// `mlen` is larger than 255.
+    pub long_form: bool,
+    /// Whether the tag supports the READ MULTIPLE BLOCKS command.
+    pub multiple_block_read: bool,
+    /// Whether the tag supports the LOCK BLOCK command.
+    pub lock_block: bool,
+    /// Whether the tag needs the special frame format for write commands.
+    pub special_frame: bool,
+}
+
+impl CapabilityContainer {
+    /// Version of the mapping this crate implements, 1.0.
+    pub const VERSION: u8 = 0x40;
+    /// Access condition granting access without any security.
+    pub const ACCESS_GRANTED: u8 = 0b00;
+    /// Access condition of a data area that cannot be written.
+    pub const NO_ACCESS: u8 = 0b11;
+
+    /// Capability container of a data area of `data_area_size` bytes that can
+    /// be read and written with the commands taking a 1 byte block number. A
+    /// size that is not a multiple of 8 is rounded down, and one that even an
+    /// 8 byte capability container cannot describe is refused with
+    /// [`Error::FieldTooLong`].
+    pub fn new(data_area_size: usize) -> Result<Self> {
+        let mlen = u16::try_from(data_area_size / 8).map_err(|_| Error::FieldTooLong)?;
+        Ok(CapabilityContainer {
+            extended_commands: false,
+            version: Self::VERSION,
+            read_access: Self::ACCESS_GRANTED,
+            write_access: Self::ACCESS_GRANTED,
+            mlen,
+            long_form: data_area_size > MAX_SHORT_DATA_AREA,
+            multiple_block_read: false,
+            lock_block: false,
+            special_frame: false,
+        })
+    }
+
+    /// Reads the capability container at the start of `cc`, refused with
+    /// [`Error::InvalidCapabilityContainer`] when it does not start with an
+    /// NDEF magic number or has a major version other than 1.
+    pub fn from_bytes(cc: &[u8]) -> Result<Self> {
+        let [magic, version, mlen, features, ref rest @ ..] = *cc else {
+            return Err(Error::SliceTooShort);
+        };
+        let extended_commands = match magic {
+            MAGIC => false,
+            MAGIC_EXTENDED => true,
+            _ => return Err(Error::InvalidCapabilityContainer),
+        };
+        if version >> 6 != Self::VERSION >> 6 {
+            return Err(Error::InvalidCapabilityContainer);
+        }
+        let (mlen, long_form) = match (mlen, rest) {
+            (0, [_, _, mlen0, mlen1, ..]) => (u16::from_be_bytes([*mlen0, *mlen1]), true),
+            (0, _) => return Err(Error::SliceTooShort),
+            (mlen, _) => (u16::from(mlen), false),
+        };
+        Ok(CapabilityContainer {
+            extended_commands,
+            version: version & 0xF0,
+            read_access: (version >> 2) & 0b11,
+            write_access: version & 0b11,
+            mlen,
+            long_form,
+            multiple_block_read: features & MULTIPLE_BLOCK_READ != 0,
+            lock_block: features & LOCK_BLOCK != 0,
+            special_frame: features & SPECIAL_FRAME != 0,
+        })
+    }
+
+    pub fn to_bytes(&self) -> heapless::Vec<u8, MAX_CC_LEN> {
+        let magic = if self.extended_commands {
+            MAGIC_EXTENDED
+        } else {
+            MAGIC
+        };
+        let access = ((self.read_access & 0b11) << 2) | (self.write_access & 0b11);
+        let features = [
+            (self.multiple_block_read, MULTIPLE_BLOCK_READ),
+            (self.lock_block, LOCK_BLOCK),
+            (self.special_frame, SPECIAL_FRAME),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |features, (_, bit)| features | bit);
+        let mut cc = heapless::Vec::new();
+        let long = self.data_area_start() == MAX_CC_LEN;
+        let mlen = if long { 0 } else { self.mlen as u8 };
+        // the capacity fits the longest capability container
+        let _ = cc.extend_from_slice(&[magic, self.version | access, mlen, features]);
+        if long {
+            let _ = cc.extend_from_slice(&[0, 0]);
+            let _ = cc.extend_from_slice(&self.mlen.to_be_bytes());
+        }
+        cc
+    }
+
+    /// Byte of the memory the data area starts at, after the capability
+    /// container. A size of 0 also takes an 8 byte capability container, as a
+    /// 4 byte one uses it to announce the longer one.
+    pub fn data_area_start(&self) -> usize {
+        match self.mlen {
+            1..=0xFF if !self.long_form => 4,
+            _ => MAX_CC_LEN,
+        }
+    }
+
+    /// Bytes of the data area.
+    pub fn data_area_size(&self) -> usize {
+        8 * self.mlen as usize
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.read_access == Self::ACCESS_GRANTED
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.write_access == Self::ACCESS_GRANTED
+    }
+}
+
+/// Capability container at the start of `memory` and the range of its data
+/// area, cut short at the end of `memory`.
+fn data_area(memory: &[u8]) -> Result<(CapabilityContainer, Range<usize>)> {
+    let cc = CapabilityContainer::from_bytes(memory)?;
+    let start = cc.data_area_start();
+    let end = (start + cc.data_area_size()).min(memory.len());
+    Ok((cc, start..end.max(start)))
+}
+
+/// Serialized NDEF message of the tag memory `memory`, to be parsed with
+/// [`Message::try_from`]. It is empty on a tag that was formatted but holds no
+/// message yet.
+pub fn read_ndef(memory: &[u8]) -> Result<Buffer> {
+    let (cc, data_area) = data_area(memory)?;
+    if !cc.is_readable() {
+        return Err(Error::InvalidCapabilityContainer);
+    }
+    type2::read_ndef(memory.get(data_area).ok_or(Error::SliceTooShort)?)
+}
+
+/// Writes `ndef`, a serialized NDEF message, in the data area of the tag
+/// memory `memory`, see [`type2::write_ndef`]. A capability container that
+/// denies writing is reported as [`Error::ReadOnly`].
+pub fn write_ndef(memory: &mut [u8], ndef: &[u8]) -> Result<()> {
+    let (cc, data_area) = data_area(memory)?;
+    if !cc.is_writable() {
+        return Err(Error::ReadOnly);
+    }
+    type2::write_ndef(memory.get_mut(data_area).ok_or(Error::SliceTooShort)?, ndef)
+}
+
+/// Writes `message` in the tag memory `memory`, see [`write_ndef`].
+pub fn write_message(memory: &mut [u8], message: &Message<'_>) -> Result<()> {
+    write_ndef(memory, &message.to_vec()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_container() {
+        // ICODE SLIX: 112 bytes of data area, READ MULTIPLE BLOCKS supported
+        let cc = CapabilityContainer::from_bytes(&[0xE1, 0x40, 0x0E, 0x01]).unwrap();
+        assert_eq!(
+            cc,
+            CapabilityContainer {
+                multiple_block_read: true,
+                ..CapabilityContainer::new(112).unwrap()
+            }
+        );
+        assert_eq!(&cc.to_bytes()[..], &[0xE1, 0x40, 0x0E, 0x01]);
+        assert_eq!(cc.data_area_start(), 4);
+
+        // ST25DV64K: 8 kB with the extended commands
+        let bytes = [0xE2, 0x43, 0x00, 0x19, 0x00, 0x00, 0x03, 0xFF];
+        let cc = CapabilityContainer::from_bytes(&bytes).unwrap();
+        assert!(cc.extended_commands && cc.lock_block && cc.special_frame);
+        assert!(cc.is_readable() && !cc.is_writable());
+        assert_eq!(cc.data_area_size(), 8184);
+        assert_eq!(cc.data_area_start(), 8);
+        assert_eq!(&cc.to_bytes()[..], &bytes);
+
+        assert_eq!(
+            CapabilityContainer::from_bytes(&bytes[..4]),
+            Err(Error::SliceTooShort)
+        );
+        assert_eq!(
+            CapabilityContainer::from_bytes(&[0xE1, 0x80, 0x0E, 0x01]),
+            Err(Error::InvalidCapabilityContainer)
+        );
+        assert_eq!(
+            CapabilityContainer::from_bytes(&[0xE3, 0x40, 0x0E, 0x01]),
+            Err(Error::InvalidCapabilityContainer)
+        );
+        assert_eq!(
+            CapabilityContainer::new(8 * 0x10000),
+            Err(Error::FieldTooLong)
+        );
+    }
+
+    #[test]
+    fn test_data_area() {
+        let msg = Message::builder().text("en", "hi").build().unwrap();
+        let ndef = msg.to_vec().unwrap();
+        let mut memory = [0xAA; 4 + 16 + 4];
+        memory[..4].copy_from_slice(&CapabilityContainer::new(16).unwrap().to_bytes());
+
+        write_message(&mut memory, &msg).unwrap();
+        assert_eq!(&memory[4..6], &[0x03, ndef.len() as u8]);
+        assert_eq!(memory[6 + ndef.len()], 0xFE);
+        assert_eq!(&memory[20..], &[0xAA; 4]);
+        assert_eq!(read_ndef(&memory).unwrap(), ndef);
+
+        let long = [0; 15];
+        assert_eq!(write_ndef(&mut memory, &long), Err(Error::BufferTooSmall));
+        memory[1] |= CapabilityContainer::NO_ACCESS;
+        assert_eq!(write_ndef(&mut memory, &[]), Err(Error::ReadOnly));
+        assert_eq!(read_ndef(&memory).unwrap(), ndef);
+        memory[1] |= CapabilityContainer::NO_ACCESS << 2;
+        assert_eq!(read_ndef(&memory), Err(Error::InvalidCapabilityContainer));
+    }
+}
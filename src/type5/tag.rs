@@ -0,0 +1,315 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use core::ops::Range;
+
+use super::{
+    read_ndef, write_message, write_ndef, CapabilityContainer, MAX_CC_LEN, MAX_SHORT_DATA_AREA,
+};
+use crate::{Buffer, Error, Message, Result};
+
+/// Blocks an address of the commands taking a 1 byte block number reaches.
+const MAX_SHORT_BLOCKS: usize = 0x100;
+
+/// Type 5 Tag of `N` bytes of memory held in memory, in blocks of
+/// [`VirtualTag::BLOCK_LEN`] bytes, which answers the ISO 15693 READ, WRITE
+/// and LOCK commands like a tag does: a block whose lock is set cannot be
+/// written anymore.
+///
+/// The capability container takes the first block, or the first two when the
+/// data area is larger than the 4 byte capability container describes.
+///
+/// ```
+/// use ndef::{type5::VirtualTag, Error, Message};
+///
+/// let msg = Message::builder().text("en", "hi").build().unwrap();
+/// let mut tag = VirtualTag::<112>::new([0xE0, 0x04, 1, 2, 3, 4, 5, 6]);
+/// tag.format().unwrap();
+/// tag.write_message(&msg).unwrap();
+/// tag.make_read_only().unwrap();
+/// assert_eq!(tag.write_message(&msg), Err(Error::ReadOnly));
+///
+/// let ndef = tag.read_ndef().unwrap();
+/// assert_eq!(Message::try_from(&ndef[..]).unwrap(), msg);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VirtualTag<const N: usize> {
+    uid: [u8; 8],
+    memory: [u8; N],
+    /// Lock of each block, bit `block % 8` of byte `block / 8`. Only the
+    /// first `N / BLOCK_LEN / 8` bytes are used, as stable Rust cannot size an
+    /// array from `N`.
+    locked: [u8; N],
+}
+
+impl<const N: usize> VirtualTag<N> {
+    /// Bytes of a block, the unit a Type 5 Tag is read and written in.
+    pub const BLOCK_LEN: usize = 4;
+
+    /// Blank tag with the 8 byte serial number `uid`, not formatted for NDEF.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is not a whole number of blocks, or leaves no room for a
+    /// data area of at least 8 bytes.
+    pub fn new(uid: [u8; 8]) -> Self {
+        assert!(
+            N % Self::BLOCK_LEN == 0 && N >= 4 + 8,
+            "a Type 5 Tag has at least 3 blocks"
+        );
+        VirtualTag {
+            uid,
+            memory: [0; N],
+            locked: [0; N],
+        }
+    }
+
+    pub fn uid(&self) -> &[u8; 8] {
+        &self.uid
+    }
+
+    /// The whole tag memory.
+    pub fn memory(&self) -> &[u8; N] {
+        &self.memory
+    }
+
+    /// Whether the LOCK command was sent for `block`.
+    pub fn is_locked(&self, block: usize) -> bool {
+        self.locked
+            .get(block / 8)
+            .is_some_and(|&byte| byte & (1 << (block % 8)) != 0)
+    }
+
+    /// Answers the READ SINGLE BLOCK command, reporting
+    /// [`Error::InvalidAddress`] for a block past the end of the memory.
+    pub fn read_single_block(&self, block: usize) -> Result<[u8; 4]> {
+        let mut data = [0; 4];
+        data.copy_from_slice(self.read_multiple_blocks(block, 1)?);
+        Ok(data)
+    }
+
+    /// Answers the READ MULTIPLE BLOCKS command: the `count` blocks starting
+    /// at `block`.
+    pub fn read_multiple_blocks(&self, block: usize, count: usize) -> Result<&[u8]> {
+        let range = self.blocks(block, count)?;
+        Ok(&self.memory[range])
+    }
+
+    /// Answers the WRITE SINGLE BLOCK command, reporting [`Error::ReadOnly`]
+    /// for a locked block.
+    pub fn write_single_block(&mut self, block: usize, data: [u8; 4]) -> Result<()> {
+        let range = self.blocks(block, 1)?;
+        if self.is_locked(block) {
+            return Err(Error::ReadOnly);
+        }
+        self.memory[range].copy_from_slice(&data);
+        Ok(())
+    }
+
+    /// Answers the LOCK BLOCK command, reporting [`Error::ReadOnly`] for a
+    /// block that is locked already.
+    pub fn lock_block(&mut self, block: usize) -> Result<()> {
+        self.blocks(block, 1)?;
+        if self.is_locked(block) {
+            return Err(Error::ReadOnly);
+        }
+        self.locked[block / 8] |= 1 << (block % 8);
+        Ok(())
+    }
+
+    /// Capability container of the tag, refused with
+    /// [`Error::InvalidCapabilityContainer`] while the tag is not formatted.
+    pub fn capability_container(&self) -> Result<CapabilityContainer> {
+        CapabilityContainer::from_bytes(&self.memory)
+    }
+
+    /// Formats a blank tag for NDEF: writes its capability container, then a
+    /// data area holding an empty NDEF message, reporting [`Error::ReadOnly`]
+    /// when a block they take is locked.
+    pub fn format(&mut self) -> Result<()> {
+        // a data area too large for the 4 byte form takes the 8 byte one, even
+        // when what is left after it fits the 4 byte form
+        let long_form = N - 4 > MAX_SHORT_DATA_AREA;
+        let cc = CapabilityContainer {
+            extended_commands: N / Self::BLOCK_LEN > MAX_SHORT_BLOCKS,
+            long_form,
+            multiple_block_read: true,
+            lock_block: true,
+            ..CapabilityContainer::new(N - if long_form { MAX_CC_LEN } else { 4 })?
+        };
+        let mut memory = [0; N];
+        memory[..cc.data_area_start()].copy_from_slice(&cc.to_bytes());
+        write_ndef(&mut memory, &[])?;
+        self.commit(&memory)
+    }
+
+    /// Writes `message` in the data area through WRITE commands. Nothing is
+    /// written when the capability container denies writing or a block the
+    /// message needs is locked, which is reported as [`Error::ReadOnly`].
+    pub fn write_message(&mut self, message: &Message<'_>) -> Result<()> {
+        let mut memory = self.memory;
+        write_message(&mut memory, message)?;
+        self.commit(&memory)
+    }
+
+    /// Serialized NDEF message held by the tag, see [`read_ndef`].
+    pub fn read_ndef(&self) -> Result<Buffer> {
+        read_ndef(&self.memory)
+    }
+
+    /// Makes a formatted tag read-only for good: the capability container
+    /// denies writing, then every block is locked.
+    pub fn make_read_only(&mut self) -> Result<()> {
+        let mut cc = self.capability_container()?;
+        cc.write_access = CapabilityContainer::NO_ACCESS;
+        let mut memory = self.memory;
+        memory[..cc.data_area_start()].copy_from_slice(&cc.to_bytes());
+        self.commit(&memory)?;
+        for block in 0..N / Self::BLOCK_LEN {
+            if !self.is_locked(block) {
+                self.lock_block(block)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Bytes of the `count` blocks starting at `block`.
+    fn blocks(&self, block: usize, count: usize) -> Result<Range<usize>> {
+        let start = block
+            .checked_mul(Self::BLOCK_LEN)
+            .ok_or(Error::InvalidAddress)?;
+        let end = count
+            .checked_mul(Self::BLOCK_LEN)
+            .and_then(|len| start.checked_add(len))
+            .ok_or(Error::InvalidAddress)?;
+        if count == 0 || end > N {
+            return Err(Error::InvalidAddress);
+        }
+        Ok(start..end)
+    }
+
+    /// Writes the blocks where `memory` differs from the tag memory, after
+    /// checking that none of them is locked.
+    fn commit(&mut self, memory: &[u8; N]) -> Result<()> {
+        let changed = |tag: &Self, block: usize| {
+            let range = block * Self::BLOCK_LEN..(block + 1) * Self::BLOCK_LEN;
+            memory[range.clone()] != tag.memory[range]
+        };
+        let blocks = 0..N / Self::BLOCK_LEN;
+        if blocks
+            .clone()
+            .any(|block| changed(self, block) && self.is_locked(block))
+        {
+            return Err(Error::ReadOnly);
+        }
+        for block in blocks {
+            if changed(self, block) {
+                let mut data = [0; 4];
+                data.copy_from_slice(&memory[self.blocks(block, 1)?]);
+                self.write_single_block(block, data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UID: [u8; 8] = [0xE0, 0x04, 0x01, 0x00, 0x12, 0x34, 0x56, 0x78];
+
+    #[test]
+    fn test_provisioning() {
+        let msg = Message::builder()
+            .uri("https://foundation.xyz/passport")
+            .build()
+            .unwrap();
+        let mut tag = VirtualTag::<64>::new(UID);
+        assert_eq!(tag.read_ndef(), Err(Error::InvalidCapabilityContainer));
+        tag.format().unwrap();
+        assert_eq!(
+            &tag.memory()[..8],
+            &[0xE1, 0x40, 0x07, 0x09, 0x03, 0x00, 0xFE, 0x00]
+        );
+        assert!(tag.read_ndef().unwrap().is_empty());
+
+        tag.write_message(&msg).unwrap();
+        let ndef = tag.read_ndef().unwrap();
+        assert_eq!(Message::try_from(&ndef[..]).unwrap(), msg);
+
+        tag.make_read_only().unwrap();
+        assert_eq!(tag.memory()[1], 0x43);
+        assert!((0..16).all(|block| tag.is_locked(block)));
+        assert!(!tag.is_locked(16));
+        assert_eq!(tag.write_message(&msg), Err(Error::ReadOnly));
+        assert_eq!(tag.read_ndef().unwrap(), ndef);
+    }
+
+    /// A tag whose data area outgrows the 4 byte capability container.
+    #[test]
+    fn test_large_tag() {
+        let mut tag = VirtualTag::<2064>::new(UID);
+        tag.format().unwrap();
+        let cc = tag.capability_container().unwrap();
+        assert_eq!(cc.data_area_start(), 8);
+        assert_eq!(cc.data_area_size(), 2056);
+        assert!(cc.extended_commands);
+        assert_eq!(&tag.memory()[8..12], &[0x03, 0x00, 0xFE, 0x00]);
+    }
+
+    /// What is left after an 8 byte capability container fits the 4 byte
+    /// form, which the capability container keeps to anyway.
+    #[test]
+    fn test_2048_byte_tag() {
+        let msg = Message::builder().text("en", "hi").build().unwrap();
+        let mut tag = VirtualTag::<2048>::new(UID);
+        tag.format().unwrap();
+        assert_eq!(
+            &tag.memory()[..8],
+            &[0xE2, 0x40, 0x00, 0x09, 0x00, 0x00, 0x00, 0xFF]
+        );
+        let cc = tag.capability_container().unwrap();
+        assert!(cc.long_form);
+        assert_eq!(cc.data_area_start(), 8);
+        assert_eq!(cc.data_area_size(), 2040);
+
+        tag.write_message(&msg).unwrap();
+        let ndef = tag.read_ndef().unwrap();
+        assert_eq!(Message::try_from(&ndef[..]).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut tag = VirtualTag::<16>::new(UID);
+        tag.write_single_block(3, [1, 2, 3, 4]).unwrap();
+        assert_eq!(tag.read_single_block(3), Ok([1, 2, 3, 4]));
+        assert_eq!(
+            tag.read_multiple_blocks(2, 2).unwrap(),
+            &[0, 0, 0, 0, 1, 2, 3, 4]
+        );
+        assert_eq!(tag.read_single_block(4), Err(Error::InvalidAddress));
+        assert_eq!(tag.read_multiple_blocks(3, 2), Err(Error::InvalidAddress));
+        assert_eq!(tag.read_multiple_blocks(0, 0), Err(Error::InvalidAddress));
+        assert_eq!(
+            tag.read_single_block(usize::MAX),
+            Err(Error::InvalidAddress)
+        );
+        assert_eq!(
+            tag.read_multiple_blocks(1, usize::MAX),
+            Err(Error::InvalidAddress)
+        );
+        assert_eq!(tag.lock_block(usize::MAX), Err(Error::InvalidAddress));
+        assert!(!tag.is_locked(usize::MAX));
+
+        tag.lock_block(3).unwrap();
+        assert_eq!(tag.lock_block(3), Err(Error::ReadOnly));
+        assert_eq!(tag.write_single_block(3, [0; 4]), Err(Error::ReadOnly));
+        assert_eq!(tag.read_single_block(3), Ok([1, 2, 3, 4]));
+
+        // formatting would clear the locked block, so nothing is written
+        assert_eq!(tag.format(), Err(Error::ReadOnly));
+        assert_eq!(tag.memory()[..12], [0; 12]);
+    }
+}
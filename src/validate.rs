@@ -0,0 +1,173 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(not(feature = "alloc"))]
+use heapless::Vec;
+
+use crate::{
+    check_external_type, check_language, check_media_type, Error, Field, Message, Payload, Record,
+    RecordType, MAX_FIELD_LEN,
+};
+
+/// A problem found by [`Message::validate`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Issue {
+    /// Index of the record at fault, or `None` for the message as a whole.
+    pub record: Option<usize>,
+    pub error: Error,
+}
+
+/// Problems found by [`Message::validate`]. Without `alloc` only the first 16
+/// are kept.
+#[cfg(feature = "alloc")]
+pub type Issues = Vec<Issue>;
+#[cfg(not(feature = "alloc"))]
+pub type Issues = Vec<Issue, 16>;
+
+impl Record<'_> {
+    /// Reports every problem the record has on its own, wherever it stands in
+    /// a message.
+    pub(crate) fn check(&self, report: &mut impl FnMut(Error)) {
+        if self.payload.type_len() > MAX_FIELD_LEN {
+            report(Error::FieldTooLong);
+        }
+        if self.id.as_ref().is_some_and(|id| id.len() > MAX_FIELD_LEN) {
+            report(Error::FieldTooLong);
+        }
+        if u32::try_from(self.payload.len()).is_err() {
+            report(Error::FieldTooLong);
+        }
+        let checked = match &self.payload {
+            Payload::RTD(RecordType::Text { enc, .. }) => check_language(enc),
+            Payload::RTD(RecordType::Uri { .. } | RecordType::SmartPoster(_)) => Ok(()),
+            Payload::RTD(RecordType::External { domain, type_, .. }) => {
+                check_external_type(domain, type_)
+            }
+            #[cfg(feature = "cbor")]
+            Payload::RTD(RecordType::Cbor(_)) => Ok(()),
+            Payload::Media { type_, .. } => check_media_type(type_),
+            Payload::AbsoluteUri { .. } => Ok(()),
+            Payload::Empty if self.id.is_some() => Err(Error::NonEmptyField(Field::Id)),
+            Payload::Empty | Payload::Unknown(_) => Ok(()),
+        };
+        if let Err(error) = checked {
+            report(error);
+        }
+    }
+}
+
+impl Message<'_> {
+    /// Every problem that keeps the message from being written, found without
+    /// writing it, along with IDs carried twice. An empty list means
+    /// [`Message::to_vec`] succeeds.
+    ///
+    /// ID references are not checked, as no record type of the crate refers
+    /// to another record by ID yet; such a type is to report a reference that
+    /// points nowhere through [`Message::resolve`].
+    pub fn validate(&self) -> Issues {
+        let mut issues = Issues::new();
+        let mut report = |record, error| {
+            #[cfg(feature = "alloc")]
+            issues.push(Issue { record, error });
+            #[cfg(not(feature = "alloc"))]
+            let _ = issues.push(Issue { record, error });
+        };
+        if self.records.is_empty() {
+            report(None, Error::EmptyMessage);
+        }
+        for (index, record) in self.records.iter().enumerate() {
+            record.check(&mut |error| report(Some(index), error));
+        }
+        // Only the later of two records sharing an ID is at fault, so each
+        // duplicate is reported once.
+        for index in self.duplicate_ids() {
+            report(Some(index), Error::DuplicateId);
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            let length: usize = self.records.iter().map(Record::encoded_len).sum();
+            if length > crate::Buffer::new().capacity() {
+                report(None, Error::BufferTooSmall);
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every problem is reported, each against the record it is in.
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let mut msg = Message::default();
+        assert_eq!(
+            msg.validate().as_slice(),
+            [Issue {
+                record: None,
+                error: Error::EmptyMessage
+            }]
+        );
+
+        let records = [
+            Record::new(Some(b"a"), Payload::RTD(RecordType::text("", "x"))),
+            Record::new(Some(b"b"), Payload::media("text", b"x")),
+            Record::new(
+                Some(b"a"),
+                Payload::RTD(RecordType::external("ex.com", "", b"x")),
+            ),
+            Record::new(Some(b"c"), Payload::media("text/plain", b"x")),
+        ];
+        for record in records {
+            msg.append_record(record).unwrap();
+        }
+        let issue = |record, error| Issue {
+            record: Some(record),
+            error,
+        };
+        assert_eq!(
+            msg.validate().as_slice(),
+            [
+                issue(0, Error::InvalidLanguageCode),
+                issue(1, Error::InvalidMediaType("text".into())),
+                issue(2, Error::InvalidExternalType("".into())),
+                issue(2, Error::DuplicateId),
+            ]
+        );
+        assert!(msg.to_vec().is_err());
+
+        msg.records.truncate(1);
+        msg.records[0].payload = Payload::RTD(RecordType::text("en", "x"));
+        assert!(msg.validate().is_empty());
+        assert!(msg.to_vec().is_ok());
+    }
+
+    /// Without an allocator, a message that would not fit the output buffer is
+    /// refused up front.
+    #[test]
+    #[cfg(not(feature = "alloc"))]
+    fn test_validate_buffer_capacity() {
+        let data = [0x61; 200];
+        let mut msg = Message::default();
+        let record = Record::new(
+            None,
+            Payload::RTD(RecordType::external("ex.com", "t", &data)),
+        );
+        msg.append_record(record.clone()).unwrap();
+        assert!(msg.validate().is_empty());
+        assert_eq!(msg.to_vec().unwrap().len(), msg.records()[0].encoded_len());
+        msg.append_record(record).unwrap();
+        assert_eq!(
+            msg.validate().as_slice(),
+            [Issue {
+                record: None,
+                error: Error::BufferTooSmall
+            }]
+        );
+        assert_eq!(msg.to_vec().unwrap_err(), Error::BufferTooSmall);
+    }
+}
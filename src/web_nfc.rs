@@ -0,0 +1,431 @@
+// SPDX-FileCopyrightText: © 2024 Foundation Devices, Inc. <hello@foundation.xyz>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Conversion to and from the `NDEFMessageInit` and `NDEFRecordInit`
+//! dictionaries of the [Web NFC API](https://w3c.github.io/web-nfc/), so a
+//! message reads the same in a browser and in this crate.
+//!
+//! Local `":type"` names are out of scope: they only mean something inside
+//! the record holding them, so they are refused with the error this crate
+//! gives a parsed record it does not support.
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    check_external_type, check_language, check_media_type, parse::decode_utf16, uri_prefix_code,
+    Error, Field, Message, Payload, Record, RecordType, URI_PREFIXES,
+};
+#[cfg(feature = "cbor")]
+use crate::{TypeNameFormat, CBOR_TYPE};
+
+/// Media type Web NFC gives a `"mime"` record without a `mediaType`.
+const DEFAULT_MEDIA_TYPE: &str = "application/octet-stream";
+
+/// A message as the Web NFC `NDEFMessageInit` dictionary.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NdefMessageInit {
+    pub records: Vec<NdefRecordInit>,
+}
+
+/// A record as the Web NFC `NDEFRecordInit` dictionary.
+///
+/// `record_type` is `"text"`, `"url"`, `"absolute-url"`, `"mime"`,
+/// `"smart-poster"`, `"empty"`, `"unknown"` or an external `"domain:type"`.
+/// The data of a smart poster is its nested message.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NdefRecordInit {
+    pub record_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<NdefData>,
+}
+
+/// The `data` of an [`NdefRecordInit`]: a string for text and URLs, bytes as
+/// an array of numbers, or a nested message.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NdefData {
+    Text(String),
+    Bytes(Vec<u8>),
+    Message(NdefMessageInit),
+}
+
+impl NdefData {
+    /// The bytes a record carries for this data: a string is written in
+    /// UTF-8 and a nested message serialized.
+    fn into_bytes(self) -> Result<Vec<u8>, Error> {
+        Ok(match self {
+            NdefData::Text(text) => text.into_bytes(),
+            NdefData::Bytes(bytes) => bytes,
+            NdefData::Message(init) => Message::try_from(&init)?.to_vec()?,
+        })
+    }
+}
+
+impl TryFrom<&Message<'_>> for NdefMessageInit {
+    type Error = Error;
+
+    fn try_from(msg: &Message<'_>) -> Result<Self, Error> {
+        Ok(NdefMessageInit {
+            records: msg
+                .records()
+                .iter()
+                .map(NdefRecordInit::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Web NFC IDs are strings, so a record whose ID is not UTF-8 is refused with
+/// [`Error::UTF8`]. An absolute URL record has no data beside its URL, so one
+/// carrying a payload is refused with [`Error::NonEmptyField`].
+impl TryFrom<&Record<'_>> for NdefRecordInit {
+    type Error = Error;
+
+    fn try_from(record: &Record<'_>) -> Result<Self, Error> {
+        let mut init = NdefRecordInit {
+            id: record
+                .id()
+                .map(|id| core::str::from_utf8(id).map(ToOwned::to_owned))
+                .transpose()?,
+            ..Default::default()
+        };
+        let bytes = |data: &[u8]| Some(NdefData::Bytes(data.to_vec()));
+        match &record.payload {
+            Payload::RTD(RecordType::Text { enc, txt }) => {
+                init.record_type = "text".to_owned();
+                init.encoding = Some("utf-8".to_owned());
+                init.lang = Some(enc.to_string());
+                init.data = Some(NdefData::Text(txt.to_string()));
+            }
+            Payload::RTD(RecordType::Uri { prefix, uri }) => {
+                init.record_type = "url".to_owned();
+                init.data = Some(NdefData::Text(
+                    RecordType::uri_prefix(*prefix).to_owned() + uri,
+                ));
+            }
+            Payload::RTD(RecordType::SmartPoster(ndef)) => {
+                init.record_type = "smart-poster".to_owned();
+                let nested = NdefMessageInit::try_from(&Message::try_from(&ndef[..])?)?;
+                init.data = Some(NdefData::Message(nested));
+            }
+            Payload::RTD(RecordType::External {
+                domain,
+                type_,
+                data,
+            }) => {
+                init.record_type = [domain, ":", type_].concat();
+                init.data = bytes(data);
+            }
+            #[cfg(feature = "cbor")]
+            Payload::RTD(RecordType::Cbor(data)) => {
+                init.record_type = CBOR_TYPE.to_owned();
+                init.data = bytes(data);
+            }
+            Payload::Media { type_, data } => {
+                init.record_type = "mime".to_owned();
+                init.media_type = Some(type_.to_string());
+                init.data = bytes(data);
+            }
+            Payload::AbsoluteUri { uri, data } => {
+                if !data.is_empty() {
+                    return Err(Error::NonEmptyField(Field::Payload));
+                }
+                init.record_type = "absolute-url".to_owned();
+                init.data = Some(NdefData::Text(uri.to_string()));
+            }
+            Payload::Empty => init.record_type = "empty".to_owned(),
+            Payload::Unknown(data) => {
+                init.record_type = "unknown".to_owned();
+                init.data = bytes(data);
+            }
+        }
+        Ok(init)
+    }
+}
+
+impl TryFrom<&NdefMessageInit> for Message<'static> {
+    type Error = Error;
+
+    fn try_from(init: &NdefMessageInit) -> Result<Self, Error> {
+        if init.records.is_empty() {
+            return Err(Error::EmptyMessage);
+        }
+        Ok(Message {
+            records: init
+                .records
+                .iter()
+                .map(Record::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// The record is checked like a parsed one.
+impl TryFrom<&NdefRecordInit> for Record<'static> {
+    type Error = Error;
+
+    fn try_from(init: &NdefRecordInit) -> Result<Self, Error> {
+        let record_type = init.record_type.as_str();
+        let data = init.data.clone();
+        let bytes =
+            |data: Option<NdefData>| data.map(NdefData::into_bytes).unwrap_or(Ok(Vec::new()));
+        let payload = match record_type {
+            "text" => {
+                let lang = init.lang.as_deref().unwrap_or("en");
+                check_language(lang)?;
+                let encoding = init.encoding.as_deref().unwrap_or("utf-8");
+                let txt = match (data, encoding) {
+                    (None, _) => String::new(),
+                    (Some(NdefData::Text(txt)), "utf-8") => txt,
+                    (Some(NdefData::Message(_)), _) => return Err(Error::InvalidRecordData),
+                    (Some(NdefData::Text(_)), _) => return Err(Error::UnsupportedEncoding),
+                    (Some(NdefData::Bytes(bytes)), "utf-8") => {
+                        String::from_utf8(bytes).map_err(|error| Error::UTF8(error.utf8_error()))?
+                    }
+                    (Some(NdefData::Bytes(bytes)), "utf-16" | "utf-16be") => {
+                        decode_utf16(&bytes, false)?
+                    }
+                    (Some(NdefData::Bytes(bytes)), "utf-16le") => decode_utf16(&bytes, true)?,
+                    _ => return Err(Error::UnsupportedEncoding),
+                };
+                Payload::RTD(RecordType::Text {
+                    enc: Cow::Owned(lang.to_owned()),
+                    txt: Cow::Owned(txt),
+                })
+            }
+            "url" => {
+                let Some(NdefData::Text(uri)) = data else {
+                    return Err(Error::InvalidRecordData);
+                };
+                let prefix = uri_prefix_code(uri.as_bytes());
+                Payload::RTD(RecordType::Uri {
+                    prefix: prefix as u8,
+                    uri: Cow::Owned(uri[URI_PREFIXES[prefix].len()..].to_owned()),
+                })
+            }
+            "absolute-url" => {
+                let Some(NdefData::Text(uri)) = data else {
+                    return Err(Error::InvalidRecordData);
+                };
+                Payload::AbsoluteUri {
+                    uri: Cow::Owned(uri),
+                    data: Cow::Owned(Vec::new()),
+                }
+            }
+            "smart-poster" => {
+                let Some(NdefData::Message(init)) = data else {
+                    return Err(Error::InvalidRecordData);
+                };
+                Payload::RTD(RecordType::SmartPoster(Cow::Owned(
+                    Message::try_from(&init)?.to_vec()?,
+                )))
+            }
+            "mime" => {
+                let type_ = init.media_type.as_deref().unwrap_or(DEFAULT_MEDIA_TYPE);
+                check_media_type(type_)?;
+                Payload::Media {
+                    type_: Cow::Owned(type_.to_owned()),
+                    data: Cow::Owned(bytes(data)?),
+                }
+            }
+            "empty" => {
+                if init.id.is_some() {
+                    return Err(Error::NonEmptyField(Field::Id));
+                }
+                if data.is_some() {
+                    return Err(Error::NonEmptyField(Field::Payload));
+                }
+                Payload::Empty
+            }
+            "unknown" => Payload::Unknown(Cow::Owned(bytes(data)?)),
+            _ => match record_type.split_once(':') {
+                #[cfg(feature = "cbor")]
+                Some(_) if TypeNameFormat::NfcExternal.type_eq(record_type, CBOR_TYPE) => {
+                    Payload::RTD(RecordType::Cbor(Cow::Owned(bytes(data)?)))
+                }
+                Some((domain, type_)) if !domain.is_empty() => {
+                    check_external_type(domain, type_)?;
+                    Payload::RTD(RecordType::External {
+                        domain: Cow::Owned(domain.to_owned()),
+                        type_: Cow::Owned(type_.to_owned()),
+                        data: Cow::Owned(bytes(data)?),
+                    })
+                }
+                // a local type only means something inside another record
+                _ => return Err(Error::UnsupportedRecordType(record_type.into())),
+            },
+        };
+        Ok(Record {
+            id: init
+                .id
+                .as_ref()
+                .map(|id| Cow::Owned(id.as_bytes().to_owned())),
+            payload,
+            chunk_size: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_nfc_round_trip() {
+        let msg = Message::builder()
+            .text("fr", "salut")
+            .id(b"greeting")
+            .uri("https://www.example.com")
+            .external("example.com", "t", b"\x00\xff")
+            .media("text/plain", b"x")
+            .build()
+            .unwrap();
+        let init = NdefMessageInit::try_from(&msg).unwrap();
+        assert_eq!(
+            serde_json::to_value(&init).unwrap(),
+            serde_json::json!({ "records": [
+                {
+                    "recordType": "text",
+                    "id": "greeting",
+                    "encoding": "utf-8",
+                    "lang": "fr",
+                    "data": "salut",
+                },
+                { "recordType": "url", "data": "https://www.example.com" },
+                { "recordType": "example.com:t", "data": [0, 255] },
+                { "recordType": "mime", "mediaType": "text/plain", "data": [120] },
+            ]})
+        );
+        assert_eq!(Message::try_from(&init).unwrap(), msg);
+
+        let mut msg = msg;
+        msg.records[0].set_id(Some(b"\xff"));
+        assert!(matches!(
+            NdefMessageInit::try_from(&msg),
+            Err(Error::UTF8(_))
+        ));
+    }
+
+    /// A smart poster holds its nested message serialized, and an absolute
+    /// URL is the type of a record without payload.
+    #[test]
+    fn test_web_nfc_smart_poster_and_absolute_url() {
+        let json = serde_json::json!({ "records": [
+            { "recordType": "smart-poster", "data": { "records": [
+                { "recordType": "url", "data": "https://x.com" },
+            ] } },
+            { "recordType": "absolute-url", "data": "urn:nfc:ext:x" },
+        ]});
+        let init: NdefMessageInit = serde_json::from_value(json.clone()).unwrap();
+        let msg = Message::try_from(&init).unwrap();
+        let poster = Message::builder().uri("https://x.com").build().unwrap();
+        assert_eq!(
+            msg.records[0].payload,
+            Payload::RTD(RecordType::smart_poster(&poster.to_vec().unwrap()))
+        );
+        assert_eq!(
+            msg.records[1].payload,
+            Payload::absolute_uri("urn:nfc:ext:x", b"")
+        );
+        assert_eq!(&msg.to_vec().unwrap()[..4], &[0x91, 0x02, 0x0A, b'S']);
+        let back = NdefMessageInit::try_from(&msg).unwrap();
+        assert_eq!(serde_json::to_value(back).unwrap(), json);
+
+        let mut msg = msg;
+        msg.records[1].payload = Payload::absolute_uri("urn:nfc:ext:x", b"a");
+        assert_eq!(
+            NdefMessageInit::try_from(&msg),
+            Err(Error::NonEmptyField(Field::Payload))
+        );
+    }
+
+    /// What the browser writes, defaults and nested messages included.
+    #[test]
+    fn test_web_nfc_from_browser() {
+        let init: NdefMessageInit = serde_json::from_str(
+            r#"{ "records": [
+                { "recordType": "text", "data": "hi" },
+                { "recordType": "text", "encoding": "utf-16le", "data": [104, 0, 105, 0] },
+                { "recordType": "mime", "data": [1] },
+                { "recordType": "empty" },
+                { "recordType": "Example.com:msg", "data": { "records": [
+                    { "recordType": "url", "data": "tel:123" }
+                ] } }
+            ] }"#,
+        )
+        .unwrap();
+        let msg = Message::try_from(&init).unwrap();
+        assert_eq!(
+            msg.records[0].payload,
+            Payload::RTD(RecordType::text("en", "hi"))
+        );
+        assert_eq!(
+            msg.records[1].payload,
+            Payload::RTD(RecordType::text("en", "hi"))
+        );
+        assert_eq!(
+            msg.records[2].payload,
+            Payload::media(DEFAULT_MEDIA_TYPE, b"\x01")
+        );
+        assert_eq!(msg.records[3].payload, Payload::Empty);
+        assert_eq!(
+            msg.records[4].payload,
+            Payload::RTD(RecordType::external(
+                "Example.com",
+                "msg",
+                b"\xd1\x01\x04U\x05123"
+            ))
+        );
+
+        let record = |json| {
+            Record::try_from(&serde_json::from_str::<NdefRecordInit>(json).unwrap()).unwrap_err()
+        };
+        assert_eq!(
+            record(r#"{ "recordType": "smart-poster", "data": "https://x" }"#),
+            Error::InvalidRecordData
+        );
+        assert_eq!(
+            record(r#"{ "recordType": "text", "data": { "records": [] } }"#),
+            Error::InvalidRecordData
+        );
+        assert_eq!(
+            record(r#"{ "recordType": ":act" }"#),
+            Error::UnsupportedRecordType(":act".into())
+        );
+        assert_eq!(
+            record(r#"{ "recordType": "url", "data": [104] }"#),
+            Error::InvalidRecordData
+        );
+        assert_eq!(
+            record(r#"{ "recordType": "url" }"#),
+            Error::InvalidRecordData
+        );
+        assert_eq!(
+            record(r#"{ "recordType": "absolute-url", "data": [104] }"#),
+            Error::InvalidRecordData
+        );
+        assert_eq!(
+            record(r#"{ "recordType": "text", "encoding": "utf-16", "data": "hi" }"#),
+            Error::UnsupportedEncoding
+        );
+        assert_eq!(
+            record(r#"{ "recordType": "empty", "id": "x" }"#),
+            Error::NonEmptyField(Field::Id)
+        );
+    }
+}